@@ -0,0 +1,272 @@
+mod session;
+#[cfg(test)]
+mod test;
+
+use bili_lib::Credentials;
+use bili_lib::{yuan, BiliClient, Order, Project, QrLogin, ORDER_PAGE_SIZE};
+use clap::{Parser, Subcommand};
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use serde::Serialize;
+use serde_json::json;
+use session::{
+    default_session_path, legacy_session_path, load_session, migrate_legacy_session, save_session,
+};
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+const LOGIN_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Parser)]
+#[command(name = "bili_cli", about = "b站会员购命令行工具")]
+pub struct Cli {
+    #[arg(long, global = true, help = "以JSON格式输出")]
+    pub json: bool,
+    #[arg(
+        long,
+        global = true,
+        env = "BILI_SESSION",
+        help = "登录凭据文件，加密保存，默认在当前用户的配置目录下"
+    )]
+    pub session: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        env = "BILI_COOKIE",
+        hide_env_values = true,
+        help = "直接指定cookie，优先于凭据文件"
+    )]
+    pub cookie: Option<String>,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    #[command(about = "扫码登录，二维码显示在终端中")]
+    Login,
+    #[command(about = "查看票品信息")]
+    Project { id: u64 },
+    #[command(about = "查看订单列表")]
+    Orders {
+        #[arg(long, default_value_t = 0, help = "页码，从0开始")]
+        page: u32,
+        #[arg(long, help = "加载全部订单", conflicts_with = "page")]
+        all: bool,
+    },
+    #[command(about = "查看订单详情")]
+    Info { order_id: String },
+    #[command(about = "取消订单")]
+    Cancel { order_id: String },
+    #[command(about = "获取订单的支付二维码")]
+    Pay { order_id: String },
+}
+
+impl Cli {
+    pub fn session_path(&self) -> PathBuf {
+        self.session.clone().unwrap_or_else(default_session_path)
+    }
+}
+
+//用上下半块字符画二维码，每行字符对应两行模块
+pub fn qr_text(data: &str) -> Option<String> {
+    let code = QrCode::new(data.as_bytes()).ok()?;
+    Some(
+        code.render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .build(),
+    )
+}
+
+pub fn order_line(order: &Order) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}张\t¥{}\t{}",
+        order.order_id,
+        order.ctime,
+        order.item_info.name,
+        order.item_info.screen_name,
+        order.count,
+        yuan(order.pay_money as i64),
+        order.status_name()
+    )
+}
+
+pub fn project_lines(project: &Project) -> Vec<String> {
+    let mut lines = vec![project.name.clone()];
+    for screen in &project.screen_list {
+        lines.push(format!(
+            "场次 {}\t{}\t{}",
+            screen.id, screen.name, screen.delivery_type
+        ));
+        for ticket in &screen.ticket_list {
+            lines.push(format!(
+                "  票档 {}\t{}\t¥{}\t{}",
+                ticket.id,
+                ticket.desc,
+                yuan(ticket.price as i64),
+                ticket.is_sale
+            ));
+        }
+    }
+    lines
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), Box<dyn Error>> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+//二维码和提示信息输出到stderr，stdout只保留结果，便于脚本处理
+fn print_qr(data: &str) {
+    match qr_text(data) {
+        Some(qr) => eprintln!("{}", qr),
+        None => eprintln!("生成二维码失败，请直接打开链接: {}", data),
+    }
+}
+
+async fn login(cli: &Cli, client: &BiliClient) -> Result<(), Box<dyn Error>> {
+    let qrcode = client.generate_qrcode().await?;
+    print_qr(&qrcode.url);
+    eprintln!("请使用b站客户端扫描二维码登录");
+    let mut last_code = 0;
+    loop {
+        tokio::time::sleep(LOGIN_POLL_INTERVAL).await;
+        let login = client.qrcode_login(&qrcode.qrcode_key).await?;
+        match login {
+            QrLogin {
+                code: 0,
+                cookie: Some(cookie),
+                refresh_token,
+                ..
+            } => {
+                client.set_cookie_jar(cookie);
+                save_session(
+                    &cli.session_path(),
+                    &Credentials {
+                        cookie: client.cookie(),
+                        refresh_token,
+                    },
+                )?;
+                break;
+            }
+            QrLogin { code: 86038, .. } => return Err("二维码已失效，请重新登录".into()),
+            QrLogin { code, message, .. } => {
+                if code != last_code {
+                    eprintln!("{}", message);
+                    last_code = code;
+                }
+            }
+        }
+    }
+    let nav = client.nav_info().await?;
+    if cli.json {
+        print_json(&json!({
+            "uname": nav.uname,
+            "session": cli.session_path(),
+        }))
+    } else {
+        println!(
+            "登录成功: {}，凭据已保存到{}",
+            nav.uname,
+            cli.session_path().display()
+        );
+        Ok(())
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let client = BiliClient::default();
+    if let Some(cookie) = &cli.cookie {
+        client.set_cookie(cookie);
+    } else {
+        let path = cli.session_path();
+        //旧版本在当前目录下明文保存凭据，使用默认位置时迁移过来
+        if cli.session.is_none() && migrate_legacy_session(legacy_session_path(), &path)? {
+            eprintln!(
+                "已将{}中的明文凭据加密保存到{}",
+                legacy_session_path().display(),
+                path.display()
+            );
+        }
+        if let Some(session) = load_session(&path)? {
+            client.set_cookie(&session.cookie);
+        }
+    }
+
+    match &cli.command {
+        Command::Login => login(&cli, &client).await?,
+        Command::Project { id } => {
+            let project = client.project_info(*id).await?;
+            if cli.json {
+                print_json(&project)?;
+            } else {
+                project_lines(&project)
+                    .iter()
+                    .for_each(|line| println!("{}", line));
+            }
+        }
+        Command::Orders { page, all } => {
+            let orders = if *all {
+                client.order_list_all().await?
+            } else {
+                client.order_list_shows(*page, ORDER_PAGE_SIZE).await?
+            };
+            if cli.json {
+                print_json(&orders)?;
+            } else {
+                orders
+                    .list
+                    .iter()
+                    .for_each(|order| println!("{}", order_line(order)));
+                println!("共{}个订单，本次显示{}个", orders.total, orders.list.len());
+            }
+        }
+        Command::Info { order_id } => {
+            let order = client.order_info(order_id).await?;
+            if cli.json {
+                print_json(&order)?;
+            } else {
+                println!("{}", order_line(&order));
+                if order.pay_remain_time > 0 {
+                    println!(
+                        "剩余支付时间 {:02}:{:02}",
+                        order.pay_remain_time / 60,
+                        order.pay_remain_time % 60
+                    );
+                }
+            }
+        }
+        Command::Cancel { order_id } => {
+            client.cancel_order(order_id).await?;
+            if cli.json {
+                print_json(&json!({"order_id": order_id, "cancelled": true}))?;
+            } else {
+                println!("已取消订单{}", order_id);
+            }
+        }
+        Command::Pay { order_id } => {
+            let code_url = client.pay_param(order_id).await?;
+            print_qr(&code_url);
+            if cli.json {
+                print_json(&json!({"order_id": order_id, "code_url": code_url}))?;
+            } else {
+                println!("{}", code_url);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
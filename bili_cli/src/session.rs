@@ -0,0 +1,65 @@
+use bili_lib::{load_credentials, save_credentials, BiliError, BiliResult, Credentials};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+//旧版本在当前目录下明文保存的凭据文件
+const LEGACY_SESSION_PATH: &str = "bili_session.json";
+
+//默认凭据文件放在当前用户的配置目录下，和图形界面一样用本机密钥加密：
+//Linux为$XDG_CONFIG_HOME/bili_ticket/cli_credentials.json，默认~/.config/bili_ticket/
+//macOS为~/Library/Application Support/bili_ticket/
+//Windows为%APPDATA%\bili_ticket\
+pub fn default_session_path() -> PathBuf {
+    config_dir()
+        .map(|dir| dir.join("bili_ticket").join("cli_credentials.json"))
+        .unwrap_or_else(|| PathBuf::from("cli_credentials.json"))
+}
+
+#[cfg(target_os = "macos")]
+fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+}
+
+#[cfg(windows)]
+fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+}
+
+//凭据文件不存在时返回None
+pub fn load_session(path: &Path) -> BiliResult<Option<Credentials>> {
+    load_credentials(path, None)
+}
+
+pub fn save_session(path: &Path, session: &Credentials) -> BiliResult<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .map_err(|e| BiliError::Credential(format!("创建目录{}失败: {}", dir.display(), e)))?;
+    }
+    save_credentials(path, session, None)
+}
+
+//把当前目录下明文的旧凭据加密保存到path，成功后删除旧文件
+pub fn migrate_legacy_session(legacy: &Path, path: &Path) -> BiliResult<bool> {
+    if path.exists() {
+        return Ok(false);
+    }
+    let Ok(bytes) = fs::read(legacy) else {
+        return Ok(false);
+    };
+    let session: Credentials = serde_json::from_slice(&bytes)?;
+    save_session(path, &session)?;
+    let _ = fs::remove_file(legacy);
+    Ok(true)
+}
+
+pub fn legacy_session_path() -> &'static Path {
+    Path::new(LEGACY_SESSION_PATH)
+}
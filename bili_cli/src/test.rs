@@ -0,0 +1,63 @@
+use crate::session::{load_session, migrate_legacy_session, save_session};
+use crate::{qr_text, yuan, Cli, Command};
+use bili_lib::Credentials;
+use clap::Parser;
+
+#[test]
+fn parses_subcommands_and_global_flags() {
+    let cli = Cli::try_parse_from(["bili_cli", "orders", "--all", "--json"]).unwrap();
+    assert!(cli.json);
+    assert!(matches!(cli.command, Command::Orders { all: true, .. }));
+
+    let cli =
+        Cli::try_parse_from(["bili_cli", "--session", "work.json", "pay", "1234567890"]).unwrap();
+    assert_eq!(cli.session_path().to_str(), Some("work.json"));
+    assert!(matches!(cli.command, Command::Pay { ref order_id } if order_id == "1234567890"));
+
+    assert!(Cli::try_parse_from(["bili_cli", "orders", "--all", "--page", "2"]).is_err());
+    assert!(Cli::try_parse_from(["bili_cli", "project", "abc"]).is_err());
+}
+
+#[test]
+fn formats_money_and_terminal_qr() {
+    assert_eq!(yuan(12800), "128.00");
+    assert_eq!(yuan(5), "0.05");
+    assert_eq!(yuan(-150), "-1.50");
+    let qr = qr_text("https://show.bilibili.com").unwrap();
+    assert!(qr.lines().count() > 10);
+}
+
+#[test]
+fn session_file_is_encrypted_and_migrates_legacy_plaintext() {
+    let dir = std::env::temp_dir().join(format!("bili_cli_session_{}", std::process::id()));
+    let path = dir.join("cli_credentials.json");
+    assert!(load_session(&path).unwrap().is_none());
+    save_session(
+        &path,
+        &Credentials {
+            cookie: "SESSDATA=sess".to_string(),
+            refresh_token: "token".to_string(),
+        },
+    )
+    .unwrap();
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("sess"));
+    let session = load_session(&path).unwrap().unwrap();
+    assert_eq!(session.cookie, "SESSDATA=sess");
+    assert_eq!(session.refresh_token, "token");
+
+    let legacy = dir.join("bili_session.json");
+    let migrated = dir.join("migrated.json");
+    std::fs::write(
+        &legacy,
+        r#"{"cookie": "SESSDATA=old", "refresh_token": "t"}"#,
+    )
+    .unwrap();
+    assert!(migrate_legacy_session(&legacy, &migrated).unwrap());
+    assert!(!legacy.exists());
+    assert_eq!(
+        load_session(&migrated).unwrap().unwrap().cookie,
+        "SESSDATA=old"
+    );
+    assert!(!migrate_legacy_session(&legacy, &migrated).unwrap());
+    let _ = std::fs::remove_dir_all(dir);
+}
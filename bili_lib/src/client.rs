@@ -0,0 +1,505 @@
+use crate::clock::local_millis;
+use crate::export::export_orders;
+use crate::refresh::{correspond_path, refresh_csrf};
+use crate::time::parse_http_date;
+use crate::{
+    Address, ApiResponse, BiliError, BiliResult, Buyer, BuyerForm, CookieInfo, CookieJar,
+    CreateForm, CreateResult, CreateStatus, ExportFormat, List, NavInfo, Order, OrderPage,
+    OrderStatus, PayParam, PrepareForm, PrepareToken, Project, QrCode, QrLogin, RefreshResult,
+    ServerClock, ServerNow, SessionHealth, NOT_LOGIN_CODE, ORDER_CREATING_ERRNO, ORDER_PAGE_SIZE,
+};
+use reqwest::header::{HeaderMap, COOKIE, DATE, SET_COOKIE};
+use reqwest::{Client, RequestBuilder};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::sleep;
+
+//服务器时间只精确到秒，相邻两次请求错开约1/3秒且不整除1000毫秒，
+//样本在服务器秒内的位置依次为0、337、674、11、348毫秒……，秒边界两侧都能采到，区间收窄得最快
+const CLOCK_SYNC_PHASE_STEP: Duration = Duration::from_millis(337);
+
+#[derive(Clone)]
+pub struct BaseUrls {
+    pub show: String,     //会员购
+    pub api: String,      //主站api
+    pub passport: String, //登录
+    pub www: String,      //主站，刷新cookie时获取refresh_csrf
+}
+
+impl Default for BaseUrls {
+    fn default() -> Self {
+        BaseUrls {
+            show: "https://show.bilibili.com".to_string(),
+            api: "https://api.bilibili.com".to_string(),
+            passport: "https://passport.bilibili.com".to_string(),
+            www: "https://www.bilibili.com".to_string(),
+        }
+    }
+}
+
+pub struct BiliClient {
+    client: Client,
+    cookie: Mutex<CookieJar>,
+    base_urls: BaseUrls,
+    clock: ServerClock,
+}
+
+impl Default for BiliClient {
+    fn default() -> Self {
+        Self::new(BaseUrls::default())
+    }
+}
+
+impl BiliClient {
+    pub fn new(base_urls: BaseUrls) -> Self {
+        Self::with_client(Client::new(), base_urls)
+    }
+
+    pub fn with_client(client: Client, base_urls: BaseUrls) -> Self {
+        BiliClient {
+            client,
+            cookie: Mutex::new(CookieJar::default()),
+            base_urls,
+            clock: ServerClock::default(),
+        }
+    }
+
+    pub fn base_urls(&self) -> &BaseUrls {
+        &self.base_urls
+    }
+
+    //经过服务器时间校准的时钟，每个响应的Date头都会用来校准
+    pub fn clock(&self) -> &ServerClock {
+        &self.clock
+    }
+
+    //Cookie请求头格式的字符串，用于保存登录状态
+    pub fn cookie(&self) -> String {
+        self.cookie.lock().unwrap().to_string()
+    }
+
+    pub fn set_cookie(&self, cookie: &str) {
+        *self.cookie.lock().unwrap() = CookieJar::parse(cookie);
+    }
+
+    pub fn cookie_jar(&self) -> CookieJar {
+        self.cookie.lock().unwrap().clone()
+    }
+
+    pub fn set_cookie_jar(&self, jar: CookieJar) {
+        *self.cookie.lock().unwrap() = jar;
+    }
+
+    pub fn cookie_value(&self, name: &str) -> Option<String> {
+        self.cookie
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(ToString::to_string)
+    }
+
+    fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let jar = self.cookie.lock().unwrap();
+        if jar.is_empty() {
+            return headers;
+        }
+        if let Ok(cookie) = jar.to_string().parse() {
+            headers.insert(COOKIE, cookie);
+        }
+        headers
+    }
+
+    fn get(&self, url: String) -> RequestBuilder {
+        self.client.get(url).headers(self.headers())
+    }
+
+    fn post(&self, url: String) -> RequestBuilder {
+        self.client.post(url).headers(self.headers())
+    }
+
+    pub async fn cancel_order(&self, order_id: &str) -> BiliResult<()> {
+        self.get_data::<IgnoredAny>(self.get(format!(
+            "{}/api/ticket/order/cancel?order_id={}",
+            self.base_urls.show, order_id
+        )))
+        .await?;
+        Ok(())
+    }
+
+    pub async fn pay_param(&self, order_id: &str) -> BiliResult<String> {
+        let data: PayParam = self
+            .get_data(self.get(format!(
+                "{}/api/ticket/order/getPayParam?order_id={}",
+                self.base_urls.show, order_id
+            )))
+            .await?;
+        Ok(data.code_url)
+    }
+
+    pub async fn order_info(&self, order_id: &str) -> BiliResult<Order> {
+        let timestamp = self.clock.now_millis();
+        self.get_data(self.get(format!(
+            "{}/api/ticket/order/info?order_id={}&timestamp={}",
+            self.base_urls.show, order_id, timestamp
+        )))
+        .await
+    }
+
+    pub async fn order_prepare(&self, prepare_form: &PrepareForm) -> BiliResult<String> {
+        let data: PrepareToken = self
+            .get_data(
+                self.post(format!("{}/api/ticket/order/prepare", self.base_urls.show))
+                    .form(prepare_form),
+            )
+            .await?;
+        Ok(data.token)
+    }
+
+    pub async fn order_create(&self, create_form: &CreateForm) -> BiliResult<u64> {
+        let data: CreateResult = self
+            .get_data(
+                self.post(format!("{}/api/ticket/order/createV2", self.base_urls.show))
+                    .form(create_form),
+            )
+            .await?;
+        Ok(data.order_id)
+    }
+
+    pub async fn order_create_status(
+        &self,
+        project_id: i64,
+        token: &str,
+        order_id: u64,
+    ) -> BiliResult<CreateStatus> {
+        let timestamp = self.clock.now_millis();
+        self.get_data(self.get(format!(
+            "{}/api/ticket/order/createstatus?project_id={}&token={}&orderId={}&timestamp={}",
+            self.base_urls.show, project_id, token, order_id, timestamp
+        )))
+        .await
+    }
+
+    //createV2返回订单号后订单可能仍在创建中，轮询直到订单状态确定，
+    //创建中时接口可能返回ORDER_CREATING_ERRNO，也可能返回成功但order_status未确定，
+    //订单被服务器取消时按失败处理，错误码为订单状态
+    pub async fn wait_order_created(
+        &self,
+        project_id: i64,
+        token: &str,
+        order_id: u64,
+        interval: Duration,
+        attempts: u32,
+    ) -> BiliResult<CreateStatus> {
+        for _ in 0..attempts {
+            match self.order_create_status(project_id, token, order_id).await {
+                Err(BiliError::Server { code, .. }) if code == ORDER_CREATING_ERRNO => {}
+                Ok(status) if !status.is_final() => {}
+                Ok(status) if status.order_status == OrderStatus::Cancelled => {
+                    return Err(BiliError::Server {
+                        code: status.order_status.code() as i64,
+                        msg: "订单已被取消".to_string(),
+                    })
+                }
+                res => return res,
+            }
+            sleep(interval).await;
+        }
+        Err(BiliError::Timeout("订单创建"))
+    }
+
+    pub async fn nav_info(&self) -> BiliResult<NavInfo> {
+        self.get_data(self.get(format!("{}/x/web-interface/nav", self.base_urls.api)))
+            .await
+    }
+
+    //服务器当前时间的unix时间戳，单位为秒
+    pub async fn server_time(&self) -> BiliResult<i64> {
+        let sent = local_millis();
+        let data: ServerNow = self
+            .get_data(
+                self.client
+                    .get(format!("{}/x/report/click/now", self.base_urls.api)),
+            )
+            .await?;
+        self.clock.add_sample(data.now, sent, local_millis());
+        Ok(data.now)
+    }
+
+    //连续请求几次服务器时间来收窄时间差的范围，返回时间差，单位为毫秒
+    pub async fn sync_clock(&self, samples: u32) -> BiliResult<i64> {
+        for i in 0..samples {
+            if i > 0 {
+                //错开请求时刻，让各个样本落在服务器秒内的不同位置
+                sleep(CLOCK_SYNC_PHASE_STEP).await;
+            }
+            self.server_time().await?;
+        }
+        Ok(self.clock.offset())
+    }
+
+    pub async fn order_list_shows(&self, page: u32, page_size: u32) -> BiliResult<OrderPage> {
+        self.get_data(self.get(format!(
+            "{}/api/ticket/order/list?page={}&page_size={}",
+            self.base_urls.show, page, page_size
+        )))
+        .await
+    }
+
+    //逐页请求，直到某一页为空或者不满一页；total可能缺失，只在有值时作为额外的上限
+    pub async fn order_list_all(&self) -> BiliResult<OrderPage> {
+        let mut all = OrderPage::default();
+        let mut page = 0;
+        loop {
+            let res = self.order_list_shows(page, ORDER_PAGE_SIZE).await?;
+            all.total = all.total.max(res.total);
+            let count = res.list.len();
+            all.list.extend(res.list);
+            if count < ORDER_PAGE_SIZE as usize || (all.total > 0 && all.list.len() >= all.total) {
+                break;
+            }
+            page += 1;
+        }
+        all.total = all.total.max(all.list.len());
+        Ok(all)
+    }
+
+    //取完所有页的订单再导出
+    pub async fn export_order_history(&self, format: ExportFormat) -> BiliResult<String> {
+        let orders = self.order_list_all().await?;
+        export_orders(&orders.list, format)
+    }
+
+    pub async fn buyer_info(&self) -> BiliResult<Vec<Buyer>> {
+        let data: List<Buyer> = self
+            .get_data(self.get(format!("{}/api/ticket/buyer/list", self.base_urls.show)))
+            .await?;
+        Ok(data.list)
+    }
+
+    pub async fn buyer_create(&self, buyer_form: &BuyerForm) -> BiliResult<()> {
+        self.get_data::<IgnoredAny>(
+            self.post(format!("{}/api/ticket/buyer/create", self.base_urls.show))
+                .form(buyer_form),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn buyer_edit(&self, buyer_form: &BuyerForm) -> BiliResult<()> {
+        self.get_data::<IgnoredAny>(
+            self.post(format!("{}/api/ticket/buyer/edit", self.base_urls.show))
+                .form(buyer_form),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn buyer_delete(&self, buyer_id: i64) -> BiliResult<()> {
+        self.get_data::<IgnoredAny>(
+            self.post(format!("{}/api/ticket/buyer/delete", self.base_urls.show))
+                .form(&[("id", buyer_id)]),
+        )
+        .await?;
+        Ok(())
+    }
+
+    //默认购票人没有单独的接口，用编辑接口把is_default置为1
+    pub async fn buyer_set_default(&self, buyer: &Buyer) -> BiliResult<()> {
+        let mut buyer_form = BuyerForm::from(buyer);
+        buyer_form.is_default = 1;
+        self.buyer_edit(&buyer_form).await
+    }
+
+    pub async fn address_list(&self) -> BiliResult<Vec<Address>> {
+        let data: List<Address> = self
+            .get_data(self.get(format!("{}/api/ticket/addr/list", self.base_urls.show)))
+            .await?;
+        Ok(data.list)
+    }
+
+    pub async fn address_add(&self, address: &Address) -> BiliResult<()> {
+        self.get_data::<IgnoredAny>(
+            self.post(format!("{}/api/ticket/addr/add", self.base_urls.show))
+                .form(address),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn address_edit(&self, address: &Address) -> BiliResult<()> {
+        self.get_data::<IgnoredAny>(
+            self.post(format!("{}/api/ticket/addr/edit", self.base_urls.show))
+                .form(address),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn generate_qrcode(&self) -> BiliResult<QrCode> {
+        self.get_data(self.client.get(format!(
+            "{}/x/passport-login/web/qrcode/generate",
+            self.base_urls.passport
+        )))
+        .await
+    }
+
+    pub async fn qrcode_login(&self, qrcode_key: &str) -> BiliResult<QrLogin> {
+        let (head, mut data): (HeaderMap, QrLogin) = self
+            .send(self.client.get(format!(
+                "{}/x/passport-login/web/qrcode/poll?qrcode_key={}",
+                self.base_urls.passport, qrcode_key
+            )))
+            .await?;
+        //每个Set-Cookie头只带一个cookie，跳转链接的query里也带有登录cookie
+        let mut jar = CookieJar::default();
+        for set_cookie in head.get_all(SET_COOKIE) {
+            if let Ok(set_cookie) = set_cookie.to_str() {
+                jar.add_set_cookie(set_cookie);
+            }
+        }
+        jar.add_url_query(&data.url);
+        data.cookie = Some(jar).filter(|jar| !jar.is_empty());
+
+        Ok(data)
+    }
+
+    //nav判断登录是否有效，有效时再询问passport是否需要刷新cookie
+    pub async fn session_health(&self) -> BiliResult<SessionHealth> {
+        match self.nav_info().await {
+            Ok(nav) if nav.is_login => {}
+            Ok(_) => return Ok(SessionHealth::Expired),
+            Err(BiliError::Server { code, .. }) if code == NOT_LOGIN_CODE => {
+                return Ok(SessionHealth::Expired)
+            }
+            Err(e) => return Err(e),
+        }
+        //旧配置迁移来的cookie没有bili_jct，登录仍然有效
+        if self.cookie_value("bili_jct").is_none() {
+            return Ok(SessionHealth::ValidNoRefresh);
+        }
+        let info = self.cookie_info().await?;
+        Ok(if info.refresh {
+            SessionHealth::NeedsRefresh
+        } else {
+            SessionHealth::Valid
+        })
+    }
+
+    async fn cookie_info(&self) -> BiliResult<CookieInfo> {
+        self.get_data(self.get(format!(
+            "{}/x/passport-login/web/cookie/info?csrf={}",
+            self.base_urls.passport,
+            self.csrf()?
+        )))
+        .await
+    }
+
+    fn csrf(&self) -> BiliResult<String> {
+        self.cookie_value("bili_jct")
+            .ok_or(BiliError::MissingField("cookie bili_jct"))
+    }
+
+    //用登录时得到的refresh_token刷新cookie，新cookie写入cookie jar，返回新的refresh_token
+    pub async fn refresh_cookie(&self, refresh_token: &str) -> BiliResult<String> {
+        let info = self.cookie_info().await?;
+        let res = self
+            .get(format!(
+                "{}/correspond/1/{}",
+                self.base_urls.www,
+                correspond_path(info.timestamp)?
+            ))
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(BiliError::Status(res.status()));
+        }
+        let html = res.text().await?;
+        let refresh_csrf = refresh_csrf(&html).ok_or(BiliError::MissingField("refresh_csrf"))?;
+
+        let (head, data): (HeaderMap, RefreshResult) = self
+            .send(
+                self.post(format!(
+                    "{}/x/passport-login/web/cookie/refresh",
+                    self.base_urls.passport
+                ))
+                .form(&[
+                    ("csrf", self.csrf()?.as_str()),
+                    ("refresh_csrf", refresh_csrf),
+                    ("source", "main_web"),
+                    ("refresh_token", refresh_token),
+                ]),
+            )
+            .await?;
+        {
+            let mut jar = self.cookie.lock().unwrap();
+            for set_cookie in head.get_all(SET_COOKIE) {
+                if let Ok(set_cookie) = set_cookie.to_str() {
+                    jar.add_set_cookie(set_cookie);
+                }
+            }
+        }
+
+        //用新的csrf确认刷新，旧的refresh_token随之失效
+        self.get_data::<IgnoredAny>(
+            self.post(format!(
+                "{}/x/passport-login/web/confirm/refresh",
+                self.base_urls.passport
+            ))
+            .form(&[
+                ("csrf", self.csrf()?.as_str()),
+                ("refresh_token", refresh_token),
+            ]),
+        )
+        .await?;
+        Ok(data.refresh_token)
+    }
+
+    pub async fn project_info(&self, project_id: u64) -> BiliResult<Project> {
+        let mut project: Project = self
+            .get_data(self.client.get(format!(
+                "{}/api/ticket/project/get?id={}",
+                self.base_urls.show, project_id
+            )))
+            .await?;
+        let performance_image: Value = serde_json::from_str(&project.performance_image)?;
+        let performance_image_url = performance_image
+            .get("first")
+            .and_then(|first| first.get("url"))
+            .and_then(Value::as_str)
+            .ok_or(BiliError::MissingField("performance_image.first.url"))?;
+        project.performance_image = "http:".to_string() + performance_image_url;
+
+        Ok(project)
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+    ) -> BiliResult<(HeaderMap, T)> {
+        let sent = local_millis();
+        let res = request.send().await?;
+        if let Some(date) = res
+            .headers()
+            .get(DATE)
+            .and_then(|date| date.to_str().ok())
+            .and_then(parse_http_date)
+        {
+            self.clock.add_header_sample(date, sent, local_millis());
+        }
+        let status = res.status();
+        if !status.is_success() {
+            return Err(BiliError::Status(status));
+        }
+        let headers = res.headers().clone();
+        let bytes = res.bytes().await?;
+        let response: ApiResponse<T> = serde_json::from_slice(&bytes)?;
+        Ok((headers, response.into_result()?))
+    }
+
+    async fn get_data<T: DeserializeOwned>(&self, request: RequestBuilder) -> BiliResult<T> {
+        Ok(self.send(request).await?.1)
+    }
+}
@@ -0,0 +1,97 @@
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn local_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+//连续这么多个样本都和当前区间不相交，才认为本地时钟被调整过，用新样本重新估计
+const OUTLIER_LIMIT: u32 = 3;
+
+//一组样本的差值区间，单位为毫秒
+#[derive(Default)]
+struct Estimate {
+    bounds: Option<(i64, i64)>,
+    outliers: u32, //连续被拒绝的样本数
+}
+
+impl Estimate {
+    fn add(&mut self, low: i64, high: i64) {
+        match self.bounds {
+            Some((old_low, old_high)) if low.max(old_low) <= high.min(old_high) => {
+                self.bounds = Some((low.max(old_low), high.min(old_high)));
+                self.outliers = 0;
+            }
+            //单个不相交的样本多半是缓存或延迟的响应，先丢弃
+            Some(_) if self.outliers + 1 < OUTLIER_LIMIT => self.outliers += 1,
+            _ => {
+                self.bounds = Some((low, high));
+                self.outliers = 0;
+            }
+        }
+    }
+}
+
+//服务器时间与本地时间的差值估计，单位为毫秒
+//服务器返回的时间只精确到秒，且不知道请求在往返途中的哪一刻被处理，
+//所以每个样本只能确定差值所在的一个区间，多个样本的区间取交集逐步收窄
+//响应头Date可能来自CDN缓存，只在还没有请求过服务器时间接口时使用，两者分开估计
+#[derive(Default)]
+pub struct ServerClock {
+    server: Mutex<Estimate>,
+    header: Mutex<Estimate>,
+}
+
+fn sample_bounds(server: i64, sent: i64, received: i64) -> (i64, i64) {
+    (server * 1000 - received, server * 1000 + 999 - sent)
+}
+
+impl ServerClock {
+    //服务器时间接口的样本，server为服务器时间戳，单位为秒，sent和received为本地发出请求和收到响应的毫秒时间戳
+    pub fn add_sample(&self, server: i64, sent: i64, received: i64) {
+        let (low, high) = sample_bounds(server, sent, received);
+        self.server.lock().unwrap().add(low, high);
+    }
+
+    //响应头Date的样本，参数同add_sample
+    pub fn add_header_sample(&self, server: i64, sent: i64, received: i64) {
+        let (low, high) = sample_bounds(server, sent, received);
+        self.header.lock().unwrap().add(low, high);
+    }
+
+    fn bounds(&self) -> Option<(i64, i64)> {
+        self.server
+            .lock()
+            .unwrap()
+            .bounds
+            .or(self.header.lock().unwrap().bounds)
+    }
+
+    pub fn reset(&self) {
+        *self.server.lock().unwrap() = Estimate::default();
+        *self.header.lock().unwrap() = Estimate::default();
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.bounds().is_some()
+    }
+
+    //差值取区间中点，未同步时为0
+    pub fn offset(&self) -> i64 {
+        self.bounds()
+            .map(|(low, high)| (low + high) / 2)
+            .unwrap_or(0)
+    }
+
+    //差值的最大误差
+    pub fn uncertainty(&self) -> Option<i64> {
+        self.bounds().map(|(low, high)| (high - low) / 2)
+    }
+
+    pub fn now_millis(&self) -> i64 {
+        local_millis() + self.offset()
+    }
+}
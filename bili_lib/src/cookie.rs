@@ -0,0 +1,103 @@
+use std::fmt::{Display, Formatter};
+
+//扫码登录跳转链接里除了cookie以外的参数
+const NON_COOKIE_PARAMS: [&str; 3] = ["gourl", "first_domain", "Expires"];
+//Set-Cookie的属性，旧版本把整段Set-Cookie当作cookie保存过
+const SET_COOKIE_ATTRIBUTES: [&str; 7] = [
+    "Path", "Domain", "Expires", "Max-Age", "HttpOnly", "Secure", "SameSite",
+];
+
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct CookieJar {
+    cookies: Vec<(String, String)>, //保持插入顺序，同名cookie覆盖旧值
+}
+
+impl CookieJar {
+    //解析请求头格式的cookie，例如"SESSDATA=xxx; bili_jct=xxx"，跳过混进来的Set-Cookie属性
+    pub fn parse(header: &str) -> Self {
+        let mut jar = CookieJar::default();
+        for pair in header.split(';') {
+            let name = pair.split('=').next().unwrap_or_default().trim();
+            if !SET_COOKIE_ATTRIBUTES
+                .iter()
+                .any(|attribute| attribute.eq_ignore_ascii_case(name))
+            {
+                jar.add_pair(pair);
+            }
+        }
+        jar
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.cookies
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn insert(&mut self, name: &str, value: &str) {
+        match self.cookies.iter_mut().find(|(n, _)| n == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.cookies.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let index = self.cookies.iter().position(|(n, _)| n == name)?;
+        Some(self.cookies.remove(index).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.cookies
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    //Set-Cookie只取第一段name=value，Path、Expires等属性不属于cookie本身
+    pub fn add_set_cookie(&mut self, set_cookie: &str) {
+        if let Some(pair) = set_cookie.split(';').next() {
+            self.add_pair(pair);
+        }
+    }
+
+    //扫码登录成功后的跳转链接在query里带有全部登录cookie
+    pub fn add_url_query(&mut self, url: &str) {
+        let Some((_, query)) = url.split_once('?') else {
+            return;
+        };
+        let query = query.split('#').next().unwrap_or_default();
+        for pair in query.split('&') {
+            if let Some((name, _)) = pair.split_once('=') {
+                if !NON_COOKIE_PARAMS.contains(&name) {
+                    self.add_pair(pair);
+                }
+            }
+        }
+    }
+
+    fn add_pair(&mut self, pair: &str) {
+        if let Some((name, value)) = pair.split_once('=') {
+            let name = name.trim();
+            if !name.is_empty() {
+                self.insert(name, value.trim());
+            }
+        }
+    }
+}
+
+//输出为Cookie请求头的格式
+impl Display for CookieJar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, (name, value)) in self.cookies.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{BiliError, BiliResult};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+//需要加密保存的登录凭据，和普通设置分开存放，图形界面和命令行共用同一格式
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Credentials {
+    pub cookie: String,
+    #[serde(default)]
+    pub refresh_token: String, //刷新cookie时使用
+}
+
+#[derive(Serialize, Deserialize)]
+struct SealedCredentials {
+    version: u32,
+    passphrase: bool, //为true时需要用户输入密码解锁，否则使用本机派生的密钥
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+//版本1的本机密钥取自环境变量，从桌面和终端启动时可能不同，只用来读取旧文件
+const SEALED_VERSION: u32 = 2;
+
+//本机派生密钥的材料，只用机器id和系统用户id，不读环境变量，
+//凭据文件被拷到其他电脑或用户下无法解密
+fn local_secret() -> Vec<u8> {
+    let mut secret = String::from("bili_ticket");
+    if let Some(machine_id) = machine_id() {
+        secret.push_str(&machine_id);
+    }
+    #[cfg(unix)]
+    secret.push_str(&unsafe { libc::getuid() }.to_string());
+    secret.into_bytes()
+}
+
+#[cfg(target_os = "linux")]
+fn machine_id() -> Option<String> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn machine_id() -> Option<String> {
+    let output = std::process::Command::new("ioreg")
+        .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+        .output()
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let line = output
+        .lines()
+        .find(|line| line.contains("IOPlatformUUID"))?;
+    line.split('"').nth(3).map(ToString::to_string)
+}
+
+#[cfg(windows)]
+fn machine_id() -> Option<String> {
+    winreg::RegKey::predef(winreg::enums::HKEY_LOCAL_MACHINE)
+        .open_subkey("SOFTWARE\\Microsoft\\Cryptography")
+        .and_then(|key| key.get_value("MachineGuid"))
+        .ok()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn machine_id() -> Option<String> {
+    None
+}
+
+fn legacy_local_secret() -> Vec<u8> {
+    let mut secret = String::from("bili_ticket");
+    for key in ["COMPUTERNAME", "HOSTNAME", "USERNAME", "USER"] {
+        if let Ok(value) = std::env::var(key) {
+            secret.push_str(&value);
+        }
+    }
+    if let Ok(machine_id) = fs::read_to_string("/etc/machine-id") {
+        secret.push_str(machine_id.trim());
+    }
+    secret.into_bytes()
+}
+
+fn derive_key(secret: &[u8], salt: &[u8]) -> BiliResult<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(secret, salt, &mut key)
+        .map_err(|e| BiliError::Credential(format!("派生密钥失败: {}", e)))?;
+    Ok(key)
+}
+
+pub fn is_passphrase_protected(path: &Path) -> bool {
+    fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<SealedCredentials>(&bytes).ok())
+        .map(|sealed| sealed.passphrase)
+        .unwrap_or(false)
+}
+
+//passphrase为None时使用本机派生的密钥
+pub fn save_credentials(
+    path: &Path,
+    credentials: &Credentials,
+    passphrase: Option<&str>,
+) -> BiliResult<()> {
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+    let secret = passphrase.map_or_else(local_secret, |p| p.as_bytes().to_vec());
+    let key = derive_key(&secret, &salt)?;
+    let plaintext = serde_json::to_vec(credentials)?;
+    let ciphertext = Aes256Gcm::new(&key.into())
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_ref())
+        .map_err(|_| BiliError::Credential("加密凭据失败".to_string()))?;
+    let sealed = SealedCredentials {
+        version: SEALED_VERSION,
+        passphrase: passphrase.is_some(),
+        salt: STANDARD.encode(salt),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    };
+    let json = serde_json::to_string(&sealed)?;
+    //凭据文件只允许当前用户读写
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(json.as_bytes()))
+        .map_err(|e| BiliError::Credential(format!("写入凭据文件失败: {}", e)))
+}
+
+//凭据文件不存在时返回None
+pub fn load_credentials(path: &Path, passphrase: Option<&str>) -> BiliResult<Option<Credentials>> {
+    let Ok(bytes) = fs::read(path) else {
+        return Ok(None);
+    };
+    let sealed: SealedCredentials = serde_json::from_slice(&bytes).map_err(|e| format_error(&e))?;
+    let secret = match (sealed.passphrase, passphrase) {
+        (true, Some(p)) => p.as_bytes().to_vec(),
+        (true, None) => {
+            return Err(BiliError::Credential(
+                "凭据已加密，需要输入密码解锁".to_string(),
+            ))
+        }
+        (false, _) if sealed.version < SEALED_VERSION => legacy_local_secret(),
+        (false, _) => local_secret(),
+    };
+    let decode = |s: &str| STANDARD.decode(s).map_err(|e| format_error(&e));
+    let salt = decode(&sealed.salt)?;
+    let nonce = decode(&sealed.nonce)?;
+    let ciphertext = decode(&sealed.ciphertext)?;
+    if nonce.len() != 12 {
+        return Err(BiliError::Credential("凭据文件格式错误".to_string()));
+    }
+    let key = derive_key(&secret, &salt)?;
+    let plaintext = Aes256Gcm::new(&key.into())
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| BiliError::Credential("解密凭据失败，密码错误或凭据文件已损坏".to_string()))?;
+    serde_json::from_slice(&plaintext)
+        .map(Some)
+        .map_err(|e| format_error(&e))
+}
+
+fn format_error(e: &dyn std::fmt::Display) -> BiliError {
+    BiliError::Credential(format!("凭据文件格式错误: {}", e))
+}
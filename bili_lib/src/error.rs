@@ -0,0 +1,53 @@
+use reqwest::StatusCode;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum BiliError {
+    Transport(reqwest::Error),         //网络请求失败，超时、断线等
+    Status(StatusCode),                //服务器返回非2xx状态码
+    Decode(serde_json::Error),         //返回内容无法解析
+    MissingField(&'static str),        //返回内容缺少字段
+    Server { code: i64, msg: String }, //服务器返回的errno/code不为0
+    Timeout(&'static str),             //轮询超过次数仍未得到结果
+    Crypto(String),                    //刷新cookie时加密失败
+    Credential(String),                //读写本地凭据文件失败
+}
+
+impl Display for BiliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BiliError::Transport(e) => write!(f, "网络请求失败: {}", e),
+            BiliError::Status(status) => write!(f, "服务器返回异常状态码: {}", status),
+            BiliError::Decode(e) => write!(f, "解析返回数据失败: {}", e),
+            BiliError::MissingField(field) => write!(f, "返回数据缺少字段: {}", field),
+            BiliError::Server { code, msg } => write!(f, "服务器返回错误({}): {}", code, msg),
+            BiliError::Timeout(what) => write!(f, "等待{}超时", what),
+            BiliError::Crypto(e) => write!(f, "加密失败: {}", e),
+            BiliError::Credential(e) => f.write_str(e),
+        }
+    }
+}
+
+impl std::error::Error for BiliError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BiliError::Transport(e) => Some(e),
+            BiliError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for BiliError {
+    fn from(e: reqwest::Error) -> Self {
+        BiliError::Transport(e)
+    }
+}
+
+impl From<serde_json::Error> for BiliError {
+    fn from(e: serde_json::Error) -> Self {
+        BiliError::Decode(e)
+    }
+}
+
+pub type BiliResult<T> = Result<T, BiliError>;
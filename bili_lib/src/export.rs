@@ -0,0 +1,127 @@
+use crate::{format_show_time, BiliResult, Order};
+use serde::Serialize;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+//导出用的一行订单，金额单位为元，时间为北京时间
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct OrderRecord {
+    pub order_id: String,
+    pub project: String,
+    pub screen: String,
+    pub ticket_type: String,
+    pub count: i32,
+    pub total_money: String,
+    pub pay_money: String,
+    pub express_fee: String,
+    pub pay_channel: String,
+    pub status: String,
+    pub create_time: String,
+    pub pay_time: String,
+}
+
+const CSV_HEADER: [&str; 12] = [
+    "订单号",
+    "票品",
+    "场次",
+    "票档",
+    "数量",
+    "总金额",
+    "实付金额",
+    "运费",
+    "支付方式",
+    "状态",
+    "下单时间",
+    "支付时间",
+];
+
+//金额单位为分，转成两位小数的元
+pub fn yuan(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}.{:02}", sign, cents.abs() / 100, cents.abs() % 100)
+}
+
+impl From<&Order> for OrderRecord {
+    fn from(order: &Order) -> Self {
+        let pay_time = if order.pay_time > 0 {
+            format_show_time(order.pay_time as i64)
+        } else {
+            String::default()
+        };
+        OrderRecord {
+            order_id: order.order_id.clone(),
+            project: order.item_info.name.clone(),
+            screen: order.item_info.screen_name.clone(),
+            ticket_type: order.item_info.ticket_type_name.clone(),
+            count: order.count,
+            total_money: yuan(order.total_money as i64),
+            pay_money: yuan(order.pay_money as i64),
+            express_fee: yuan(order.express_fee.max(0) as i64),
+            pay_channel: order.pay_channel.to_string(),
+            status: order.status_name(),
+            create_time: order.create_time(),
+            pay_time,
+        }
+    }
+}
+
+//含逗号、引号或换行的字段用引号包起来，引号写两遍
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn orders_to_csv(orders: &[Order]) -> String {
+    //带BOM，Excel才能正确识别UTF-8中文
+    let mut csv = String::from("\u{feff}");
+    csv.push_str(&CSV_HEADER.join(","));
+    csv.push_str("\r\n");
+    for record in orders.iter().map(OrderRecord::from) {
+        let fields = [
+            record.order_id,
+            record.project,
+            record.screen,
+            record.ticket_type,
+            record.count.to_string(),
+            record.total_money,
+            record.pay_money,
+            record.express_fee,
+            record.pay_channel,
+            record.status,
+            record.create_time,
+            record.pay_time,
+        ];
+        let line: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&line.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+pub fn orders_to_json(orders: &[Order]) -> BiliResult<String> {
+    let records: Vec<OrderRecord> = orders.iter().map(OrderRecord::from).collect();
+    Ok(serde_json::to_string_pretty(&records)?)
+}
+
+pub fn export_orders(orders: &[Order], format: ExportFormat) -> BiliResult<String> {
+    match format {
+        ExportFormat::Csv => Ok(orders_to_csv(orders)),
+        ExportFormat::Json => orders_to_json(orders),
+    }
+}
@@ -0,0 +1,130 @@
+//测试用的接口返回数据，bili_lib和bili_ticket的测试共用，需要开启test-util特性
+use crate::{OrderStatus, OrderSubStatus};
+use serde_json::{json, Value};
+
+//status跟状态名称保持一致，sub_status只确认了待支付的取值，其它状态不填
+pub fn order_json(order_id: &str, sub_status_name: &str) -> Value {
+    let status = match sub_status_name {
+        "待支付" => OrderStatus::Unpaid,
+        "已取消" => OrderStatus::Cancelled,
+        _ => OrderStatus::Paid,
+    };
+    let mut order = json!({
+        "order_id": order_id,
+        "uid": "12345",
+        "order_type": 1,
+        "item_id": 77777,
+        "item_info": {
+            "name": "BilibiliWorld 2024",
+            "img": "//i0.hdslb.com/bfs/img.jpg",
+            "screen_id": 1001,
+            "screen_name": "7月12日",
+            "express_fee": 0,
+            "express_free_flag": 0,
+            "deliver_type": 1,
+            "screen_type": 1,
+            "link_id": 0,
+            "ticket_type": 1,
+            "time": 1720764000,
+            "ticket_type_name": "普通票"
+        },
+        "count": 1,
+        "total_money": 19800,
+        "pay_money": 19800,
+        "express_fee": 0,
+        "pay_channel": 0,
+        "status": status.code(),
+        "refund_status": 0,
+        "pay_time": 0,
+        "ctime": "2024-03-18 20:00:00",
+        "source": "pc",
+        "ticket_agent": "",
+        "img": {"url": "//i0.hdslb.com/bfs/img.jpg", "desc": ""},
+        "current_time": 1710763200,
+        "deliver_type_name": "电子票",
+        "free_deliver": false,
+        "create_at": 1710763200,
+        "pay_remain_time": 900,
+        "sub_status_name": sub_status_name
+    });
+    if status == OrderStatus::Unpaid {
+        order["sub_status"] = json!(OrderSubStatus::Unpaid.code());
+    }
+    order
+}
+
+pub fn project_json() -> Value {
+    json!({
+        "buyer_info": "",
+        "need_contact": 1,
+        "name": "BilibiliWorld 2024",
+        "status": 1,
+        "is_sale": 1,
+        "start_time": 1720764000,
+        "end_time": 1720972800,
+        "sale_begin": 1710763200,
+        "sale_end": 1720972800,
+        "sale_start": 1710763200,
+        "performance_image": "{\"first\":{\"url\":\"//i0.hdslb.com/bfs/first.jpg\"}}",
+        "screen_list": [screen_json(1001, "7月12日", vec![ticket_json(2001, "普通票", 1, true)])]
+    })
+}
+
+pub fn screen_json(id: i64, name: &str, ticket_list: Vec<Value>) -> Value {
+    json!({
+        "id": id,
+        "delivery_type": 1,
+        "start_time": 1720764000,
+        "name": name,
+        "type": 1,
+        "ticket_type": 1,
+        "screen_type": 1,
+        "ticket_list": ticket_list
+    })
+}
+
+pub fn ticket_json(id: i64, desc: &str, is_sale: i32, clickable: bool) -> Value {
+    json!({
+        "id": id,
+        "anonymous_buy": false,
+        "price": 19800,
+        "desc": desc,
+        "sale_start": "2024-03-18 20:00:00",
+        "sale_end": "2024-07-14 18:00:00",
+        "sale_type": 1,
+        "is_sale": is_sale,
+        "screen_name": "7月12日",
+        "clickable": clickable
+    })
+}
+
+pub fn buyer_json(id: i64, name: &str, is_default: i8) -> Value {
+    json!({
+        "id": id,
+        "uid": 12345,
+        "account_channel": "",
+        "personal_id": "110101********0011",
+        "name": name,
+        "id_card_front": "",
+        "id_card_back": "",
+        "is_default": is_default,
+        "tel": "138****0000",
+        "error_code": 0,
+        "id_type": 0,
+        "verify_status": 1,
+        "accountId": 12345
+    })
+}
+
+pub fn address_json(id: i64, def: i8) -> Value {
+    json!({
+        "id": id,
+        "name": "张三",
+        "phone": "13800000000",
+        "prov": "上海市",
+        "city": "上海市",
+        "area": "浦东新区",
+        "addr": "世纪大道1号",
+        "def": def
+    })
+}
@@ -1,10 +1,32 @@
-use reqwest::header::HeaderMap;
-use reqwest::Client;
+mod client;
+mod clock;
+mod cookie;
+mod credential;
+mod error;
+mod export;
+#[cfg(feature = "test-util")]
+pub mod fixture;
+mod refresh;
+mod response;
+mod status;
+#[cfg(test)]
+mod test;
+mod time;
+
+pub use client::{BaseUrls, BiliClient};
+pub use clock::{local_millis, ServerClock};
+pub use cookie::CookieJar;
+pub use credential::{is_passphrase_protected, load_credentials, save_credentials, Credentials};
+pub use error::{BiliError, BiliResult};
+pub use export::{export_orders, orders_to_csv, orders_to_json, yuan, ExportFormat, OrderRecord};
+pub use response::ApiResponse;
 use serde::ser::SerializeStruct;
 use serde::{Deserialize, Serialize, Serializer};
-use serde_json::Error;
-use std::string::ToString;
-use std::time::{SystemTime, UNIX_EPOCH};
+pub use status::{
+    DeliveryType, OrderStatus, OrderSubStatus, PayChannel, ProjectStatus, RefundStatus, SaleStatus,
+    SaleType,
+};
+pub use time::{format_show_time, parse_show_time};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Buyer {
@@ -23,28 +45,90 @@ pub struct Buyer {
     #[serde(rename = "accountId")]
     account_id: i64, //同uid
 }
-#[derive(Serialize, Deserialize, Clone)]
+
+impl Buyer {
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+    pub fn uid(&self) -> i64 {
+        self.uid
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn personal_id(&self) -> &str {
+        &self.personal_id
+    }
+    pub fn tel(&self) -> &str {
+        &self.tel
+    }
+    pub fn id_type(&self) -> i64 {
+        self.id_type
+    }
+    pub fn is_default(&self) -> bool {
+        self.is_default == 1
+    }
+    pub fn error_code(&self) -> i64 {
+        self.error_code
+    }
+    pub fn verify_status(&self) -> i64 {
+        self.verify_status
+    }
+}
+
+//新增和编辑购票人的表单，id为0时表示新增
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct BuyerForm {
+    #[serde(skip_serializing_if = "is_zero")]
+    pub id: i64,
+    pub name: String,
+    pub personal_id: String,
+    pub tel: String,
+    pub id_type: i64, //0为身份证
+    pub is_default: i8,
+}
+
+fn is_zero(id: &i64) -> bool {
+    *id == 0
+}
+
+impl From<&Buyer> for BuyerForm {
+    fn from(buyer: &Buyer) -> Self {
+        BuyerForm {
+            id: buyer.id,
+            name: buyer.name.clone(),
+            personal_id: buyer.personal_id.clone(),
+            tel: buyer.tel.clone(),
+            id_type: buyer.id_type,
+            is_default: buyer.is_default,
+        }
+    }
+}
+//订单列表和订单详情的字段不完全相同，缺少的字段取默认值
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct ItemInfo {
     pub name: String,
     img: String,
     screen_id: i32,
-    screen_name: String,
+    pub screen_name: String,
     express_fee: i32,
     express_free_flag: i32,
-    deliver_type: i32,
+    pub deliver_type: DeliveryType,
     screen_type: i32,
     //project_ver_id: i64,
     link_id: i32,
     ticket_type: i32,
     time: i32,
-    ticket_type_name: String,
+    pub ticket_type_name: String,
 }
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct Img {
     url: String,
     desc: String,
 }
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct Order {
     pub order_id: String,
     uid: String,
@@ -52,46 +136,88 @@ pub struct Order {
     item_id: i32,
     #[serde(rename = "item_info")]
     pub item_info: ItemInfo,
-    count: i32,
-    total_money: i32,
-    pay_money: i32,
-    express_fee: i32,
-    pay_channel: i32,
-    status: i32,
-    sub_status: i32,
-    refund_status: i32,
-    pay_time: i32,
-    ctime: String,
+    pub count: i32,
+    pub total_money: i32, //总金额，单位为分
+    pub pay_money: i32,   //实付金额，单位为分
+    pub express_fee: i32, //运费，单位为分，免运费时为0或-1
+    pub pay_channel: PayChannel,
+    pub status: OrderStatus,
+    pub sub_status: OrderSubStatus,
+    pub refund_status: RefundStatus,
+    pub pay_time: i32,
+    pub ctime: String, //下单时间，格式为yyyy-MM-dd HH:mm:ss
     source: String,
     ticket_agent: String,
     img: Img,
-    current_time: i32,
-    deliver_type_name: String,
+    pub current_time: i32, //返回订单时的服务器时间戳
+    pub deliver_type_name: String,
     free_deliver: bool,
-    create_at: i32,
-    pay_remain_time: i32,
+    pub create_at: i32,       //下单时间戳
+    pub pay_remain_time: i32, //剩余支付时间，单位为秒
     pub sub_status_name: String,
 }
 
+impl Order {
+    pub fn is_unpaid(&self) -> bool {
+        self.status == OrderStatus::Unpaid
+    }
+
+    //优先使用服务器返回的状态名称
+    pub fn status_name(&self) -> String {
+        if self.sub_status_name.is_empty() {
+            self.sub_status.to_string()
+        } else {
+            self.sub_status_name.clone()
+        }
+    }
+
+    //有下单时间戳时按北京时间格式化，否则用服务器给的字符串
+    pub fn create_time(&self) -> String {
+        if self.create_at > 0 {
+            format_show_time(self.create_at as i64)
+        } else {
+            self.ctime.clone()
+        }
+    }
+
+    //待支付订单的支付截止时间，为服务器时间的unix时间戳，单位为秒
+    pub fn pay_deadline(&self) -> Option<i64> {
+        if !self.is_unpaid() || self.current_time <= 0 {
+            return None;
+        }
+        Some(self.current_time as i64 + self.pay_remain_time.max(0) as i64)
+    }
+
+    pub fn delivery_name(&self) -> String {
+        if self.deliver_type_name.is_empty() {
+            self.item_info.deliver_type.to_string()
+        } else {
+            self.deliver_type_name.clone()
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Project {
     pub buyer_info: String, //“2,1”为实名认证
     pub need_contact: i32,  //需要联系人表单吗
     pub name: String,
-    status: i32,
-    is_sale: i32,
+    pub status: ProjectStatus,
+    pub is_sale: SaleStatus,
     start_time: u64,
     end_time: u64,
-    sale_begin: i64,
-    sale_end: u64,
-    sale_start: u64,
+    pub sale_begin: i64, //以下均为unix时间戳，单位为秒
+    pub sale_end: u64,
+    pub sale_start: u64,
     pub performance_image: String,
     pub screen_list: Vec<Screen>,
 }
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Screen {
     pub id: i64,
-    pub delivery_type: i32, //配送方式，1为电子票，3为纸质票
+    pub delivery_type: DeliveryType, //配送方式
+    #[serde(default)]
+    pub express_fee: i32, //纸质票运费，单位为分，免运费时为0或-1
     start_time: u64,
     pub name: String,
     #[serde(rename = "type")]
@@ -106,14 +232,25 @@ pub struct Ticket {
     pub anonymous_buy: bool, //匿名购买
     pub price: u64,
     pub desc: String,
-    sale_start: String,
-    sale_end: String,
-    sale_type: i32,
-    pub is_sale: i32,
+    pub sale_start: String, //开售时间，格式为yyyy-MM-dd HH:mm:ss，北京时间
+    pub sale_end: String,
+    pub sale_type: SaleType,
+    pub is_sale: SaleStatus,
     screen_name: String,
-    #[serde(rename = "clickable")]
-    click_able: bool,
+    pub clickable: bool, //票档按钮能否点击，售罄或未开售时为false
 }
+impl Ticket {
+    //开售时间的unix时间戳，单位为秒
+    pub fn sale_start_at(&self) -> Option<i64> {
+        parse_show_time(&self.sale_start)
+    }
+
+    //可点击且在售才能下单
+    pub fn is_purchasable(&self) -> bool {
+        self.clickable && self.is_sale == SaleStatus::OnSale
+    }
+}
+
 #[derive(Serialize, Clone, Default)]
 pub struct PrepareForm {
     pub project_id: i64,
@@ -140,8 +277,63 @@ pub struct CreateForm {
     pub new_risk: bool,
     //#[serde(rename = "requestSource")]
     pub request_source: String, //电脑为pc-new
-    pub buyer: String,          //联系人姓名
-    pub tel: String,            //联系人电话
+    pub order_form: OrderForm,
+}
+
+//不同票型需要提交的购票人信息
+pub enum OrderForm {
+    Anonymous, //匿名购买，无需购票人信息
+    NamePhone {
+        buyer: String, //联系人姓名
+        tel: String,   //联系人电话
+    },
+    Buyer(Vec<Buyer>), //实名购票人，数量需和购票数量一致
+    Deliver {
+        buyer: String, //联系人姓名
+        tel: String,   //联系人电话
+        deliver_info: DeliverInfo,
+    },
+}
+
+//纸质票的收货信息
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct DeliverInfo {
+    pub name: String,
+    pub tel: String,
+    pub addr_id: i64,
+    pub addr: String, //省市区和详细地址拼接后的完整地址
+}
+
+impl From<&Address> for DeliverInfo {
+    fn from(address: &Address) -> Self {
+        DeliverInfo {
+            name: address.name.clone(),
+            tel: address.phone.clone(),
+            addr_id: address.id,
+            addr: address.full_address(),
+        }
+    }
+}
+
+//收货地址，id为0时表示新增
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Address {
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub id: i64,
+    pub name: String,  //收件人
+    pub phone: String, //收件人电话
+    pub prov: String,
+    pub city: String,
+    pub area: String,
+    pub addr: String, //详细地址
+    #[serde(default)]
+    pub def: i8, //是否为默认地址
+}
+
+impl Address {
+    pub fn full_address(&self) -> String {
+        format!("{}{}{}{}", self.prov, self.city, self.area, self.addr)
+    }
 }
 
 #[derive(Serialize)]
@@ -169,223 +361,142 @@ impl Serialize for CreateForm {
         state.serialize_field("deviceId", &self.device_id)?;
         state.serialize_field(
             "clickPosition",
-            &serde_json::to_string(&self.click_position).unwrap(),
+            &serde_json::to_string(&self.click_position).map_err(serde::ser::Error::custom)?,
         )?;
         state.serialize_field("newRisk", &self.new_risk)?;
         state.serialize_field("requestSource", &self.request_source)?;
-        state.serialize_field("buyer", &self.buyer)?;
-        state.serialize_field("tel", &self.tel)?;
+        match &self.order_form {
+            OrderForm::Anonymous => {}
+            OrderForm::NamePhone { buyer, tel } => {
+                state.serialize_field("buyer", buyer)?;
+                state.serialize_field("tel", tel)?;
+            }
+            OrderForm::Buyer(buyers) => {
+                state.serialize_field(
+                    "buyer_info",
+                    &serde_json::to_string(buyers).map_err(serde::ser::Error::custom)?,
+                )?;
+            }
+            OrderForm::Deliver {
+                buyer,
+                tel,
+                deliver_info,
+            } => {
+                state.serialize_field("buyer", buyer)?;
+                state.serialize_field("tel", tel)?;
+                state.serialize_field(
+                    "deliver_info",
+                    &serde_json::to_string(deliver_info).map_err(serde::ser::Error::custom)?,
+                )?;
+            }
+        }
         state.end()
     }
 }
 
-pub async fn cancel_order(
-    client: &Client,
-    headers: HeaderMap,
-    order_id: &String,
-) -> Result<(), ()> {
-    let res = client
-        .get("https://show.bilibili.com/api/ticket/order/cancel?order_id=".to_string() + order_id)
-        .headers(headers)
-        .send()
-        .await
-        .unwrap();
-    let json = res.json::<serde_json::Value>().await.unwrap();
-    let errno = json.get("errno").unwrap();
-    if errno.as_i64().unwrap() == 0 {
-        Ok(())
-    } else {
-        Err(())
-    }
+pub const ORDER_PAGE_SIZE: u32 = 20;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct OrderPage {
+    #[serde(default)]
+    pub total: usize, //订单总数
+    pub list: Vec<Order>,
 }
 
-pub async fn pay_param(
-    client: &Client,
-    headers: HeaderMap,
-    order_id: &String,
-) -> Result<String, ()> {
-    let res = client
-        .get(
-            "https://show.bilibili.com/api/ticket/order/getPayParam?order_id=".to_string()
-                + order_id,
-        )
-        .headers(headers)
-        .send()
-        .await
-        .unwrap();
-    let json = res.json::<serde_json::Value>().await.unwrap();
-    let data = json.get("data").unwrap();
-    if let Some(url) = data.get("code_url") {
-        return Ok(url.as_str().unwrap().to_string());
-    } else {
-        return Err(());
-    }
+#[derive(Deserialize)]
+pub(crate) struct List<T> {
+    pub(crate) list: Vec<T>,
 }
 
-pub async fn order_info(client: &Client, headers: HeaderMap, order_id: String) -> Order {
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis();
-    let res = client
-        .get(format!(
-            "https://show.bilibili.com/api/ticket/order/info?order_id={}&timestamp={}",
-            order_id, timestamp
-        ))
-        .headers(headers)
-        .send()
-        .await
-        .unwrap();
-    res.json::<Order>().await.unwrap()
-}
-
-pub async fn order_prepare(
-    client: &Client,
-    headers: HeaderMap,
-    prepare_form: &PrepareForm,
-) -> Result<String, ()> {
-    let res = client
-        .post("https://show.bilibili.com/api/ticket/order/prepare")
-        .headers(headers)
-        .form(prepare_form)
-        .send()
-        .await
-        .unwrap();
-    let json = res.json::<serde_json::Value>().await.unwrap();
-    let data = json.get("data").unwrap();
-    let token = data.get("token").unwrap().as_str().unwrap().to_string();
-    Ok(token)
-}
-
-pub async fn order_create(
-    client: &Client,
-    headers: HeaderMap,
-    create_form: &CreateForm,
-) -> Result<u64, String> {
-    let res = client
-        .post("https://show.bilibili.com/api/ticket/order/createV2")
-        .headers(headers)
-        .form(create_form)
-        .send()
-        .await
-        .unwrap();
-    let json = res.json::<serde_json::Value>().await.unwrap();
-    let data = json.get("data").unwrap();
-    if let Some(order_id) = data.get("orderId") {
-        Ok(order_id.as_u64().unwrap())
-    } else {
-        Err(json.get("msg").unwrap().as_str().unwrap().to_string())
-    }
+#[derive(Deserialize)]
+pub(crate) struct PayParam {
+    pub(crate) code_url: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct PrepareToken {
+    pub(crate) token: String,
 }
 
-pub async fn nav_info(client: &Client, headers: HeaderMap) -> (String, String) {
-    let res = client
-        .get("https://api.bilibili.com/x/web-interface/nav")
-        .headers(headers)
-        .send()
-        .await
-        .unwrap();
-    let json = res.json::<serde_json::Value>().await.unwrap();
-    let data = json.get("data").unwrap();
-    (
-        data.get("uname").unwrap().as_str().unwrap().to_string(),
-        data.get("face").unwrap().as_str().unwrap().to_string(),
-    )
-}
-
-pub async fn order_list_shows(client: &Client, headers: HeaderMap) -> Vec<Order> {
-    let res = client
-        .get("https://show.bilibili.com/api/ticket/order/list?page=0&page_size=20")
-        .headers(headers.clone())
-        .send()
-        .await
-        .unwrap();
-    let json = res.json::<serde_json::Value>().await.unwrap();
-    let data = json.get("data").unwrap();
-    let order_list: Vec<Order> = serde_json::from_value(data.get("list").unwrap().clone()).unwrap();
-    order_list
-}
-
-pub async fn buyer_info(client: Client, headers: HeaderMap) -> Vec<Buyer> {
-    let res = client
-        .get("https://show.bilibili.com/api/ticket/buyer/list")
-        .headers(headers)
-        .send()
-        .await
-        .unwrap();
-    let json = res.json::<serde_json::Value>().await.unwrap();
-    let data = json.get("data").unwrap();
-    let buyer_list: Vec<Buyer> = serde_json::from_value(data.get("list").unwrap().clone()).unwrap();
-    buyer_list
-}
-pub async fn generate_qrcode(client: &Client) -> (String, String) {
-    let res = client
-        .get("https://passport.bilibili.com/x/passport-login/web/qrcode/generate")
-        .send()
-        .await
-        .unwrap();
-    let json = res.json::<serde_json::Value>().await.unwrap();
-    let data = json.get("data").unwrap();
-    (
-        data.get("url").unwrap().as_str().unwrap().to_string(),
-        data.get("qrcode_key")
-            .unwrap()
-            .as_str()
-            .unwrap()
-            .to_string(),
-    )
-}
-
-pub async fn qrcode_login(client: &Client, qrcode_key: &String) -> (i64, String, Option<String>) {
-    let res = client
-        .get(
-            "https://passport.bilibili.com/x/passport-login/web/qrcode/poll?qrcode_key="
-                .to_string()
-                + qrcode_key,
+#[derive(Deserialize)]
+pub(crate) struct CreateResult {
+    #[serde(rename = "orderId")]
+    pub(crate) order_id: u64,
+}
+
+//订单创建后仍在排队处理时createstatus返回的errno
+//抓包观察到的“订单创建中”错误码，未见官方说明，因此返回成功但状态未确定时也继续轮询
+pub const ORDER_CREATING_ERRNO: i64 = 100012;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct CreateStatus {
+    #[serde(default)]
+    pub order_status: OrderStatus, //创建中时为0
+    #[serde(default)]
+    pub pay_remain_time: i32, //剩余支付时间，单位为秒
+}
+
+impl CreateStatus {
+    //订单已生成（待支付），或者已经支付、取消
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self.order_status,
+            OrderStatus::Unpaid | OrderStatus::Paid | OrderStatus::Cancelled
         )
-        .send()
-        .await
-        .unwrap();
-    let head = res.headers().clone();
-    let json = res.json::<serde_json::Value>().await.unwrap();
-    let data = json.get("data").unwrap();
-    let re_cookie: Option<String>;
-    if let Some(cookie) = head.get("Set-Cookie") {
-        re_cookie = Option::from(cookie.to_str().unwrap().to_string());
-    } else {
-        re_cookie = None;
     }
+}
 
-    (
-        data.get("code").unwrap().as_i64().unwrap(),
-        data.get("message").unwrap().as_str().unwrap().to_string(),
-        re_cookie,
-    )
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NavInfo {
+    #[serde(rename = "isLogin")]
+    pub is_login: bool,
+    #[serde(default)]
+    pub uname: String, //昵称
+    #[serde(default)]
+    pub face: String, //头像url
 }
 
-pub async fn project_info(client: &Client, project_id: u64) -> Result<Project, Error> {
-    let res = client
-        .get(
-            "https://show.bilibili.com/api/ticket/project/get?id=".to_string()
-                + &project_id.to_string(),
-        )
-        .send()
-        .await
-        .unwrap();
-    let json = res.json::<serde_json::Value>().await.unwrap();
-    let data = json.get("data").unwrap();
-    let mut project: Project = serde_json::from_value(data.clone())?;
-    let performance_image: serde_json::Value =
-        serde_json::from_str(&project.performance_image).unwrap();
-    let performance_image_url = "http:".to_string()
-        + performance_image
-            .get("first")
-            .unwrap()
-            .get("url")
-            .unwrap()
-            .as_str()
-            .unwrap();
-    project.performance_image = performance_image_url;
-
-    Ok(project)
-}
\ No newline at end of file
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QrCode {
+    pub url: String,
+    pub qrcode_key: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ServerNow {
+    pub now: i64,
+}
+
+//nav接口未登录时返回的code
+pub const NOT_LOGIN_CODE: i64 = -101;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionHealth {
+    Valid,          //登录有效
+    ValidNoRefresh, //登录有效，但cookie里没有bili_jct，无法检查和刷新
+    NeedsRefresh,   //登录有效，但服务器要求刷新cookie
+    Expired,        //登录已失效，需要重新扫码
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CookieInfo {
+    pub refresh: bool,
+    pub timestamp: i64,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RefreshResult {
+    pub refresh_token: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QrLogin {
+    pub code: i64, //0为成功，86101为未扫码，86090为已扫码未确认，86038为二维码失效
+    pub message: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub refresh_token: String,
+    #[serde(skip)]
+    pub cookie: Option<CookieJar>,
+}
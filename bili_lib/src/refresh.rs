@@ -0,0 +1,33 @@
+use crate::{BiliError, BiliResult};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Oaep, RsaPublicKey};
+use sha2::Sha256;
+
+//主站用于生成correspondPath的公钥
+const CORRESPOND_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----
+MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQDLgd2OAkcGVtoE3ThUREbio0Eg
+Uc/prcajMKXvkCKFCWhJYJcLkcM2DKKcSeFpD/j6Boy538YXnR6VhcuUJOhH2x71
+nzPjfdTcqMz7djHum0qSZA0AyCBDABUqCrfNgCiJ00Ra7GmRj+YCK1NJEuewlb40
+JNrRuoEUXpabUzGB8QIDAQAB
+-----END PUBLIC KEY-----";
+
+//用RSA-OAEP加密"refresh_{毫秒时间戳}"，结果的十六进制即为correspondPath
+pub(crate) fn correspond_path(timestamp: i64) -> BiliResult<String> {
+    let key = RsaPublicKey::from_public_key_pem(CORRESPOND_PUBLIC_KEY)
+        .map_err(|e| BiliError::Crypto(e.to_string()))?;
+    let encrypted = key
+        .encrypt(
+            &mut rand::thread_rng(),
+            Oaep::new::<Sha256>(),
+            format!("refresh_{}", timestamp).as_bytes(),
+        )
+        .map_err(|e| BiliError::Crypto(e.to_string()))?;
+    Ok(encrypted.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+//correspond页面中<div id="1-name">里的内容就是refresh_csrf
+pub(crate) fn refresh_csrf(html: &str) -> Option<&str> {
+    let start = html.find("<div id=\"1-name\">")? + "<div id=\"1-name\">".len();
+    let len = html[start..].find("</div>")?;
+    Some(html[start..start + len].trim())
+}
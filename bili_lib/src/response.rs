@@ -0,0 +1,51 @@
+use crate::{BiliError, BiliResult};
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+//show.bilibili.com返回errno/msg，api.bilibili.com和passport返回code/message
+#[derive(Deserialize)]
+struct RawResponse {
+    errno: Option<i64>,
+    code: Option<i64>,
+    msg: Option<String>,
+    message: Option<String>,
+    data: Option<Value>,
+}
+
+pub enum ApiResponse<T> {
+    Success(T),
+    Error { code: i64, message: String },
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for ApiResponse<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawResponse::deserialize(deserializer)?;
+        let code = raw
+            .errno
+            .or(raw.code)
+            .ok_or_else(|| D::Error::missing_field("errno"))?;
+        if code != 0 {
+            return Ok(ApiResponse::Error {
+                code,
+                message: raw.msg.or(raw.message).unwrap_or_default(),
+            });
+        }
+        //失败时data的结构不固定，所以只在成功时才解析data
+        let data =
+            serde_json::from_value(raw.data.unwrap_or(Value::Null)).map_err(D::Error::custom)?;
+        Ok(ApiResponse::Success(data))
+    }
+}
+
+impl<T> ApiResponse<T> {
+    pub fn into_result(self) -> BiliResult<T> {
+        match self {
+            ApiResponse::Success(data) => Ok(data),
+            ApiResponse::Error { code, message } => Err(BiliError::Server { code, msg: message }),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+
+//接口里用整数表示的状态，未列出的取值保存在Unknown里，序列化时还原为原来的整数
+macro_rules! status_enum {
+    ($name:ident { $($variant:ident = $code:literal => $display:literal,)* }) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum $name {
+            $($variant,)*
+            Unknown(i32),
+        }
+
+        impl $name {
+            pub fn code(self) -> i32 {
+                match self {
+                    $($name::$variant => $code,)*
+                    $name::Unknown(code) => code,
+                }
+            }
+        }
+
+        impl From<i32> for $name {
+            fn from(code: i32) -> Self {
+                match code {
+                    $($code => $name::$variant,)*
+                    code => $name::Unknown(code),
+                }
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name::from(0)
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    $($name::$variant => f.write_str($display),)*
+                    $name::Unknown(code) => write!(f, "未知({})", code),
+                }
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_i32(self.code())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                i32::deserialize(deserializer).map($name::from)
+            }
+        }
+    };
+}
+
+//Order.status
+status_enum!(OrderStatus {
+    Unpaid = 1 => "待支付",
+    Paid = 2 => "已支付",
+    Cancelled = 4 => "已取消",
+});
+
+//Order.sub_status，取值和status不是一套，只确认了待支付，其它状态显示时以sub_status_name为准
+status_enum!(OrderSubStatus {
+    Unpaid = 1 => "待支付",
+});
+
+status_enum!(RefundStatus {
+    NotRefunded = 0 => "未退款",
+    Refunding = 1 => "退款中",
+    Refunded = 2 => "已退款",
+});
+
+status_enum!(PayChannel {
+    Unpaid = 0 => "未支付",
+    Alipay = 1 => "支付宝",
+    Wechat = 2 => "微信支付",
+});
+
+//Screen.delivery_type
+status_enum!(DeliveryType {
+    Electronic = 1 => "电子票",
+    Paper = 3 => "纸质票",
+});
+
+//Ticket.sale_type
+status_enum!(SaleType {
+    Normal = 1 => "普通销售",
+    Presale = 2 => "预售",
+});
+
+//Ticket.is_sale和Project.is_sale
+status_enum!(SaleStatus {
+    NotOnSale = 0 => "不可售",
+    OnSale = 1 => "可售",
+});
+
+//Project.status
+status_enum!(ProjectStatus {
+    Offline = 0 => "已下架",
+    Normal = 1 => "正常",
+});
@@ -0,0 +1,207 @@
+use crate::refresh::{correspond_path, refresh_csrf};
+use crate::time::parse_http_date;
+use crate::{
+    format_show_time, is_passphrase_protected, load_credentials, parse_show_time, save_credentials,
+    ApiResponse, BiliError, CookieJar, Credentials, DeliveryType, NavInfo, OrderStatus, PayChannel,
+    QrCode, ServerClock,
+};
+use serde_json::json;
+
+fn decode<T: serde::de::DeserializeOwned>(json: serde_json::Value) -> Result<T, BiliError> {
+    serde_json::from_value::<ApiResponse<T>>(json)?.into_result()
+}
+
+#[test]
+fn api_response_decodes_show_and_passport_success() {
+    let qrcode: QrCode = decode(json!({
+        "code": 0,
+        "message": "0",
+        "data": {"url": "https://passport.bilibili.com/h5-app/passport/login/scan", "qrcode_key": "key"}
+    }))
+    .unwrap();
+    assert_eq!(qrcode.qrcode_key, "key");
+
+    let nav: NavInfo = decode(json!({
+        "errno": 0,
+        "msg": "",
+        "data": {"isLogin": true, "uname": "bili", "face": "https://i0.hdslb.com/face.jpg"}
+    }))
+    .unwrap();
+    assert!(nav.is_login);
+    assert_eq!(nav.uname, "bili");
+}
+
+#[test]
+fn api_response_reports_server_error_without_decoding_data() {
+    match decode::<QrCode>(json!({"errno": 100001, "msg": "前方拥堵，请稍后再试", "data": {}}))
+    {
+        Err(BiliError::Server { code, msg }) => {
+            assert_eq!(code, 100001);
+            assert_eq!(msg, "前方拥堵，请稍后再试");
+        }
+        _ => panic!("expected server error"),
+    }
+    match decode::<NavInfo>(
+        json!({"code": -101, "message": "账号未登录", "data": {"isLogin": false}}),
+    ) {
+        Err(BiliError::Server { code, msg }) => {
+            assert_eq!(code, -101);
+            assert_eq!(msg, "账号未登录");
+        }
+        _ => panic!("expected server error"),
+    }
+}
+
+#[test]
+fn api_response_rejects_missing_code_and_bad_payload() {
+    assert!(matches!(
+        decode::<QrCode>(json!({"data": {}})),
+        Err(BiliError::Decode(_))
+    ));
+    assert!(matches!(
+        decode::<QrCode>(json!({"code": 0, "data": {"url": 1}})),
+        Err(BiliError::Decode(_))
+    ));
+}
+
+#[test]
+fn cookie_jar_parses_header_and_overwrites_by_name() {
+    let mut jar = CookieJar::parse("SESSDATA=old; bili_jct=csrf;");
+    jar.add_set_cookie("SESSDATA=new; Path=/; Domain=.bilibili.com; HttpOnly");
+    assert_eq!(jar.get("SESSDATA"), Some("new"));
+    assert_eq!(jar.to_string(), "SESSDATA=new; bili_jct=csrf");
+    assert_eq!(jar.remove("bili_jct").as_deref(), Some("csrf"));
+    assert!(CookieJar::parse("").is_empty());
+}
+
+#[test]
+fn cookie_jar_skips_attributes_in_legacy_set_cookie() {
+    let jar = CookieJar::parse(
+        "SESSDATA=sess; Path=/; Domain=.bilibili.com; Expires=Wed, 18 Sep 2024 12:00:00 GMT; \
+         max-age=15551000; HttpOnly; Secure; SameSite=None; bili_jct=csrf",
+    );
+    assert_eq!(jar.to_string(), "SESSDATA=sess; bili_jct=csrf");
+}
+
+#[test]
+fn correspond_path_and_refresh_csrf() {
+    let path = correspond_path(1710763200000).unwrap();
+    assert_eq!(path.len(), 256);
+    assert!(path.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(
+        refresh_csrf("<div id=\"1-name\"> b0cc8411ded2f9db2cff2edb3123acac </div>"),
+        Some("b0cc8411ded2f9db2cff2edb3123acac")
+    );
+    assert_eq!(refresh_csrf("<div id=\"2-name\">x</div>"), None);
+}
+
+#[test]
+fn show_time_is_beijing_time() {
+    assert_eq!(parse_show_time("2024-03-18 20:00:00"), Some(1710763200));
+    assert_eq!(format_show_time(1710763200), "2024-03-18 20:00:00");
+    assert_eq!(parse_show_time(""), None);
+}
+
+#[test]
+fn server_clock_narrows_offset_with_each_sample() {
+    let clock = ServerClock::default();
+    assert!(!clock.is_synced());
+    assert_eq!(clock.offset(), 0);
+
+    //服务器秒1000，本地在10_000发出、10_200收到：差值在[989_800, 990_999]
+    clock.add_sample(1000, 10_000, 10_200);
+    assert_eq!(clock.offset(), 990_399);
+    assert_eq!(clock.uncertainty(), Some(599));
+    //服务器秒1001，本地在10_700发出、10_800收到：差值在[990_200, 991_299]
+    clock.add_sample(1001, 10_700, 10_800);
+    assert_eq!(clock.offset(), 990_599);
+    assert_eq!(clock.uncertainty(), Some(399));
+
+    //单个不相交的样本当作异常值丢弃
+    clock.add_sample(1000, 0, 100);
+    assert_eq!(clock.offset(), 990_599);
+    clock.add_sample(1001, 11_000, 11_100);
+    //连续多个不相交的样本说明本地时钟被调整过，重新开始估计
+    clock.add_sample(1000, 0, 100);
+    clock.add_sample(1000, 0, 100);
+    assert_eq!(clock.offset(), 990_599);
+    clock.add_sample(1000, 0, 100);
+    assert_eq!(clock.offset(), 1_000_449);
+    clock.reset();
+    assert!(!clock.is_synced());
+}
+
+#[test]
+fn server_clock_prefers_server_time_over_date_header() {
+    let clock = ServerClock::default();
+    clock.add_header_sample(1000, 10_000, 10_200);
+    assert_eq!(clock.offset(), 990_399);
+    clock.add_sample(2000, 10_000, 10_200);
+    assert_eq!(clock.offset(), 1_990_399);
+    //过期的Date头不影响服务器时间接口得到的结果
+    for _ in 0..5 {
+        clock.add_header_sample(500, 20_000, 20_100);
+    }
+    assert_eq!(clock.offset(), 1_990_399);
+}
+
+#[test]
+fn http_date_parses_to_timestamp() {
+    assert_eq!(
+        parse_http_date("Mon, 18 Mar 2024 12:00:00 GMT"),
+        Some(1710763200)
+    );
+    assert_eq!(parse_http_date("yesterday"), None);
+}
+
+#[test]
+fn status_enums_keep_unknown_codes() {
+    assert_eq!(DeliveryType::from(3), DeliveryType::Paper);
+    assert_eq!(DeliveryType::Paper.to_string(), "纸质票");
+    assert_eq!(OrderStatus::from(9), OrderStatus::Unknown(9));
+    assert_eq!(OrderStatus::Unknown(9).to_string(), "未知(9)");
+    assert_eq!(PayChannel::default(), PayChannel::Unpaid);
+
+    let status: OrderStatus = serde_json::from_value(json!(9)).unwrap();
+    assert_eq!(serde_json::to_value(status).unwrap(), json!(9));
+    assert_eq!(serde_json::to_value(OrderStatus::Unpaid).unwrap(), json!(1));
+}
+
+#[test]
+fn credentials_round_trip_encrypted() {
+    let dir = std::env::temp_dir();
+    let local = dir.join(format!(
+        "bili_credentials_local_{}.json",
+        std::process::id()
+    ));
+    let locked = dir.join(format!("bili_credentials_pass_{}.json", std::process::id()));
+    let credentials = Credentials {
+        cookie: "SESSDATA=secret".to_string(),
+        refresh_token: "token".to_string(),
+    };
+
+    save_credentials(&local, &credentials, None).unwrap();
+    assert!(!std::fs::read_to_string(&local).unwrap().contains("secret"));
+    assert!(!is_passphrase_protected(&local));
+    //从桌面和终端启动时环境变量不同，本机密钥不能受影响
+    std::env::set_var("USER", "bili_ticket_other_shell");
+    std::env::remove_var("HOSTNAME");
+    let loaded = load_credentials(&local, None).unwrap().unwrap();
+    assert_eq!(loaded.cookie, "SESSDATA=secret");
+    assert_eq!(loaded.refresh_token, "token");
+
+    save_credentials(&locked, &credentials, Some("hunter2")).unwrap();
+    assert!(is_passphrase_protected(&locked));
+    assert!(load_credentials(&locked, None).is_err());
+    assert!(load_credentials(&locked, Some("wrong")).is_err());
+    let loaded = load_credentials(&locked, Some("hunter2")).unwrap().unwrap();
+    assert_eq!(loaded.cookie, "SESSDATA=secret");
+
+    let _ = std::fs::remove_file(local);
+    let _ = std::fs::remove_file(locked);
+    assert!(
+        load_credentials(&dir.join("bili_missing_credentials.json"), None)
+            .unwrap()
+            .is_none()
+    );
+}
@@ -0,0 +1,34 @@
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+
+//会员购接口里的时间字符串都是北京时间
+const SHOW_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn beijing() -> FixedOffset {
+    FixedOffset::east_opt(8 * 3600).unwrap()
+}
+
+//解析yyyy-MM-dd HH:mm:ss格式的北京时间，返回unix时间戳，单位为秒
+pub fn parse_show_time(time: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(time.trim(), SHOW_TIME_FORMAT)
+        .ok()?
+        .and_local_timezone(beijing())
+        .single()
+        .map(|time| time.timestamp())
+}
+
+//响应头Date的格式，例如Mon, 18 Mar 2024 12:00:00 GMT
+pub(crate) fn parse_http_date(date: &str) -> Option<i64> {
+    DateTime::parse_from_rfc2822(date.trim())
+        .ok()
+        .map(|time| time.timestamp())
+}
+
+pub fn format_show_time(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|time| {
+            time.with_timezone(&beijing())
+                .format(SHOW_TIME_FORMAT)
+                .to_string()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,1045 @@
+mod common;
+
+use bili_lib::{
+    Address, BiliError, Buyer, BuyerForm, ClickPosition, CreateForm, DeliverInfo, DeliveryType,
+    ExportFormat, OrderForm, OrderStatus, OrderSubStatus, PayChannel, PrepareForm, RefundStatus,
+    SaleStatus, SessionHealth, NOT_LOGIN_CODE, ORDER_CREATING_ERRNO, ORDER_PAGE_SIZE,
+};
+use common::*;
+use serde_json::json;
+use std::time::Duration;
+use wiremock::matchers::{body_string_contains, header, method, path, path_regex, query_param};
+use wiremock::{Mock, Request, ResponseTemplate};
+
+fn prepare_form() -> PrepareForm {
+    PrepareForm {
+        project_id: 77777,
+        screen_id: 1001,
+        sku_id: 2001,
+        order_type: 1,
+        count: 1,
+    }
+}
+
+fn create_form() -> CreateForm {
+    CreateForm {
+        project_id: 77777,
+        screen_id: 1001,
+        sku_id: 2001,
+        count: 1,
+        pay_money: 19800,
+        order_type: 1,
+        timestamp: 1710763200000,
+        token: "prepare-token".to_string(),
+        device_id: String::default(),
+        click_position: ClickPosition {
+            x: 935,
+            y: 786,
+            origin: 1710763199000,
+            now: 1710763200000,
+        },
+        new_risk: false,
+        request_source: "pc_new".to_string(),
+        order_form: OrderForm::NamePhone {
+            buyer: "张三".to_string(),
+            tel: "13800000000".to_string(),
+        },
+    }
+}
+
+#[tokio::test]
+async fn generate_qrcode_returns_url_and_key() {
+    let (server, client) = stub().await;
+    Mock::given(method("GET"))
+        .and(path("/x/passport-login/web/qrcode/generate"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(passport_ok(json!({
+            "url": "https://passport.bilibili.com/h5-app/passport/login/scan?qrcode_key=key",
+            "qrcode_key": "key"
+        }))))
+        .mount(&server)
+        .await;
+
+    let qrcode = client.generate_qrcode().await.unwrap();
+    assert_eq!(qrcode.qrcode_key, "key");
+    assert!(qrcode.url.ends_with("qrcode_key=key"));
+}
+
+#[tokio::test]
+async fn generate_qrcode_reports_server_error() {
+    let (server, client) = stub().await;
+    Mock::given(path("/x/passport-login/web/qrcode/generate"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(passport_err(-412, "请求被拦截")))
+        .mount(&server)
+        .await;
+
+    assert!(matches!(
+        client.generate_qrcode().await,
+        Err(BiliError::Server { code: -412, .. })
+    ));
+}
+
+#[tokio::test]
+async fn qrcode_login_returns_status_and_cookie() {
+    let (server, client) = stub().await;
+    Mock::given(path("/x/passport-login/web/qrcode/poll"))
+        .and(query_param("qrcode_key", "key"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .append_header("Set-Cookie", "SESSDATA=sess; Path=/; HttpOnly")
+                .append_header("Set-Cookie", "bili_jct=csrf; Path=/")
+                .append_header("Set-Cookie", "DedeUserID=42; Path=/")
+                .set_body_json(passport_ok(json!({
+                    "url": "https://passport.biligame.com/crossDomain?DedeUserID=42&DedeUserID__ckMd5=md5&Expires=15551000&SESSDATA=sess&bili_jct=csrf&gourl=https%3A%2F%2Fwww.bilibili.com",
+                    "refresh_token": "refresh",
+                    "timestamp": 1710763200000i64,
+                    "code": 0,
+                    "message": ""
+                }))),
+        )
+        .mount(&server)
+        .await;
+
+    let login = client.qrcode_login("key").await.unwrap();
+    assert_eq!(login.code, 0);
+    assert_eq!(login.refresh_token, "refresh");
+    let jar = login.cookie.unwrap();
+    assert_eq!(jar.get("SESSDATA"), Some("sess"));
+    assert_eq!(jar.get("bili_jct"), Some("csrf"));
+    assert_eq!(jar.get("DedeUserID__ckMd5"), Some("md5"));
+    assert_eq!(jar.get("gourl"), None);
+    assert_eq!(
+        jar.to_string(),
+        "SESSDATA=sess; bili_jct=csrf; DedeUserID=42; DedeUserID__ckMd5=md5"
+    );
+
+    client.set_cookie_jar(jar);
+    assert_eq!(client.cookie_value("bili_jct").as_deref(), Some("csrf"));
+}
+
+#[tokio::test]
+async fn qrcode_login_reports_pending_and_http_error() {
+    let (server, client) = stub().await;
+    Mock::given(path("/x/passport-login/web/qrcode/poll"))
+        .and(query_param("qrcode_key", "pending"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(passport_ok(json!({
+            "url": "",
+            "refresh_token": "",
+            "timestamp": 0,
+            "code": 86101,
+            "message": "未扫码"
+        }))))
+        .mount(&server)
+        .await;
+    Mock::given(path("/x/passport-login/web/qrcode/poll"))
+        .and(query_param("qrcode_key", "broken"))
+        .respond_with(ResponseTemplate::new(502))
+        .mount(&server)
+        .await;
+
+    let login = client.qrcode_login("pending").await.unwrap();
+    assert_eq!(login.code, 86101);
+    assert!(login.cookie.is_none());
+    assert!(matches!(
+        client.qrcode_login("broken").await,
+        Err(BiliError::Status(status)) if status.as_u16() == 502
+    ));
+}
+
+#[tokio::test]
+async fn project_info_decodes_project_and_image() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/project/get"))
+        .and(query_param("id", "77777"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(project_json())))
+        .mount(&server)
+        .await;
+
+    let project = client.project_info(77777).await.unwrap();
+    assert_eq!(project.name, "BilibiliWorld 2024");
+    assert_eq!(
+        project.performance_image,
+        "http://i0.hdslb.com/bfs/first.jpg"
+    );
+    assert_eq!(project.screen_list[0].ticket_list[0].price, 19800);
+    assert_eq!(
+        project.screen_list[0].delivery_type,
+        DeliveryType::Electronic
+    );
+    assert_eq!(
+        project.screen_list[0].ticket_list[0].is_sale,
+        SaleStatus::OnSale
+    );
+    assert_eq!(
+        project.screen_list[0].ticket_list[0].sale_start_at(),
+        Some(1710763200)
+    );
+    assert!(project.screen_list[0].ticket_list[0].is_purchasable());
+}
+
+#[tokio::test]
+async fn project_info_reports_missing_project_and_bad_payload() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/project/get"))
+        .and(query_param("id", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_err(-404, "项目不存在")))
+        .mount(&server)
+        .await;
+    Mock::given(path("/api/ticket/project/get"))
+        .and(query_param("id", "2"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html>"))
+        .mount(&server)
+        .await;
+
+    assert!(matches!(
+        client.project_info(1).await,
+        Err(BiliError::Server { code: -404, .. })
+    ));
+    assert!(matches!(
+        client.project_info(2).await,
+        Err(BiliError::Decode(_))
+    ));
+}
+
+#[tokio::test]
+async fn order_prepare_sends_form_with_cookie() {
+    let (server, client) = stub().await;
+    Mock::given(method("POST"))
+        .and(path("/api/ticket/order/prepare"))
+        .and(header("cookie", "SESSDATA=sess; bili_jct=csrf"))
+        .and(body_string_contains("sku_id=2001"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(show_ok(json!({"token": "prepare-token"}))),
+        )
+        .mount(&server)
+        .await;
+
+    assert_eq!(
+        client.order_prepare(&prepare_form()).await.unwrap(),
+        "prepare-token"
+    );
+}
+
+#[tokio::test]
+async fn order_prepare_reports_server_error() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/order/prepare"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(show_err(100041, "对未发售的票进行购买")),
+        )
+        .mount(&server)
+        .await;
+
+    match client.order_prepare(&prepare_form()).await {
+        Err(BiliError::Server { code, msg }) => {
+            assert_eq!(code, 100041);
+            assert_eq!(msg, "对未发售的票进行购买");
+        }
+        _ => panic!("expected server error"),
+    }
+}
+
+#[tokio::test]
+async fn order_create_returns_order_id() {
+    let (server, client) = stub().await;
+    Mock::given(method("POST"))
+        .and(path("/api/ticket/order/createV2"))
+        .and(body_string_contains("token=prepare-token"))
+        .and(body_string_contains("requestSource=pc_new"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({
+            "orderId": 1710763200123u64,
+            "orderCreateTime": 1710763200
+        }))))
+        .mount(&server)
+        .await;
+
+    assert_eq!(
+        client.order_create(&create_form()).await.unwrap(),
+        1710763200123
+    );
+}
+
+#[tokio::test]
+async fn order_create_sends_real_name_buyers() {
+    let (server, client) = stub().await;
+    Mock::given(method("POST"))
+        .and(path("/api/ticket/order/createV2"))
+        .and(body_string_contains("buyer_info=%5B%7B%22id%22%3A1%2C"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({"orderId": 1u64}))))
+        .mount(&server)
+        .await;
+
+    let mut form = create_form();
+    form.order_form = OrderForm::Buyer(vec![
+        serde_json::from_value(buyer_json(1, "张三", 1)).unwrap()
+    ]);
+    assert_eq!(client.order_create(&form).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn order_create_sends_deliver_info() {
+    let (server, client) = stub().await;
+    Mock::given(method("POST"))
+        .and(path("/api/ticket/order/createV2"))
+        .and(body_string_contains("buyer=%E5%BC%A0%E4%B8%89"))
+        .and(body_string_contains("deliver_info=%7B%22name%22"))
+        .and(body_string_contains("%22addr_id%22%3A7%2C"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({"orderId": 2u64}))))
+        .mount(&server)
+        .await;
+
+    let address: Address = serde_json::from_value(address_json(7, 1)).unwrap();
+    let mut form = create_form();
+    form.order_form = OrderForm::Deliver {
+        buyer: "张三".to_string(),
+        tel: "13800000000".to_string(),
+        deliver_info: DeliverInfo::from(&address),
+    };
+    assert_eq!(client.order_create(&form).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn order_create_sends_anonymous_form_without_buyer() {
+    let (server, client) = stub().await;
+    Mock::given(method("POST"))
+        .and(path("/api/ticket/order/createV2"))
+        .and(body_string_contains("token=prepare-token"))
+        .and(|request: &Request| {
+            let body = String::from_utf8_lossy(&request.body);
+            !body.contains("buyer") && !body.contains("tel=")
+        })
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({"orderId": 3u64}))))
+        .mount(&server)
+        .await;
+
+    let mut form = create_form();
+    form.order_form = OrderForm::Anonymous;
+    assert_eq!(client.order_create(&form).await.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn order_create_reports_server_error() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/order/createV2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_err(100009, "库存不足")))
+        .mount(&server)
+        .await;
+
+    assert!(matches!(
+        client.order_create(&create_form()).await,
+        Err(BiliError::Server { code: 100009, .. })
+    ));
+}
+
+#[tokio::test]
+async fn order_list_shows_decodes_page_and_total() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/order/list"))
+        .and(query_param("page", "0"))
+        .and(query_param("page_size", "20"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({
+            "total": 2,
+            "list": [order_json("1", "待支付"), order_json("2", "已完成")]
+        }))))
+        .mount(&server)
+        .await;
+
+    let orders = client.order_list_shows(0, ORDER_PAGE_SIZE).await.unwrap();
+    assert_eq!(orders.total, 2);
+    assert_eq!(orders.list.len(), 2);
+    assert_eq!(orders.list[0].order_id, "1");
+    assert_eq!(orders.list[1].sub_status_name, "已完成");
+    assert_eq!(orders.list[0].status, OrderStatus::Unpaid);
+    assert!(orders.list[0].is_unpaid());
+    assert_eq!(orders.list[0].sub_status, OrderSubStatus::Unpaid);
+    //已完成的订单不是待支付，也没有支付截止时间
+    assert_eq!(orders.list[1].status, OrderStatus::Paid);
+    assert!(!orders.list[1].is_unpaid());
+    assert_eq!(orders.list[1].pay_deadline(), None);
+    assert_eq!(orders.list[1].status_name(), "已完成");
+    assert_eq!(orders.list[0].refund_status, RefundStatus::NotRefunded);
+}
+
+#[tokio::test]
+async fn order_list_shows_reports_not_logged_in() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/order/list"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_err(83000004, "请先登录")))
+        .mount(&server)
+        .await;
+
+    assert!(matches!(
+        client.order_list_shows(0, ORDER_PAGE_SIZE).await,
+        Err(BiliError::Server { code: 83000004, .. })
+    ));
+}
+
+#[tokio::test]
+async fn order_list_all_walks_every_page() {
+    let (server, client) = stub().await;
+    for page in 0..3 {
+        let list: Vec<_> = (0..ORDER_PAGE_SIZE)
+            .map(|i| page * ORDER_PAGE_SIZE + i)
+            .filter(|id| *id < 45)
+            .map(|id| order_json(&id.to_string(), "已完成"))
+            .collect();
+        Mock::given(path("/api/ticket/order/list"))
+            .and(query_param("page", page.to_string()))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(show_ok(json!({"total": 45, "list": list}))),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+
+    let orders = client.order_list_all().await.unwrap();
+    assert_eq!(orders.total, 45);
+    assert_eq!(orders.list.len(), 45);
+    assert_eq!(orders.list[44].order_id, "44");
+}
+
+#[tokio::test]
+async fn order_info_decodes_detail_data() {
+    let (server, client) = stub().await;
+    let mut order = order_json("1", "待支付");
+    let detail = order.as_object_mut().unwrap();
+    //详情接口没有列表里的部分字段
+    for key in ["source", "ticket_agent", "img", "free_deliver"] {
+        detail.remove(key);
+    }
+    order["item_info"]["deliver_type"] = json!(3);
+    order["express_fee"] = json!(1000);
+    order["pay_channel"] = json!(1);
+    order["deliver_type_name"] = json!("");
+    Mock::given(path("/api/ticket/order/info"))
+        .and(query_param("order_id", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(order)))
+        .mount(&server)
+        .await;
+
+    let order = client.order_info("1").await.unwrap();
+    assert_eq!(order.order_id, "1");
+    assert_eq!(order.item_info.ticket_type_name, "普通票");
+    assert_eq!(order.item_info.deliver_type, DeliveryType::Paper);
+    assert_eq!(order.delivery_name(), "纸质票");
+    assert_eq!(order.total_money, 19800);
+    assert_eq!(order.express_fee, 1000);
+    assert_eq!(order.pay_channel, PayChannel::Alipay);
+    assert_eq!(order.create_time(), "2024-03-18 20:00:00");
+    assert_eq!(order.pay_deadline(), Some(1710763200 + 900));
+}
+
+#[tokio::test]
+async fn export_order_history_covers_every_page() {
+    let (server, client) = stub().await;
+    //第一页是满的，第二页只有一条
+    for page in 0..2 {
+        let count = if page == 0 { ORDER_PAGE_SIZE } else { 1 };
+        let list: Vec<_> = (0..count)
+            .map(|i| {
+                let mut order = order_json(&(page * ORDER_PAGE_SIZE + i).to_string(), "待支付");
+                order["item_info"]["name"] = json!("BW, 2024");
+                order["pay_money"] = json!(1990);
+                order
+            })
+            .collect();
+        Mock::given(path("/api/ticket/order/list"))
+            .and(query_param("page", page.to_string()))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(show_ok(json!({"total": 21, "list": list}))),
+            )
+            .mount(&server)
+            .await;
+    }
+
+    let csv = client
+        .export_order_history(ExportFormat::Csv)
+        .await
+        .unwrap();
+    let lines: Vec<&str> = csv.trim_start_matches('\u{feff}').lines().collect();
+    assert_eq!(lines.len(), 22);
+    assert!(lines[0].starts_with("订单号,票品,"));
+    assert_eq!(
+        lines[1],
+        "0,\"BW, 2024\",7月12日,普通票,1,198.00,19.90,0.00,未支付,待支付,2024-03-18 20:00:00,"
+    );
+
+    let json = client
+        .export_order_history(ExportFormat::Json)
+        .await
+        .unwrap();
+    let records: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+    assert_eq!(records.len(), 21);
+    assert_eq!(records[20]["order_id"], "20");
+    assert_eq!(records[20]["pay_money"], "19.90");
+    assert_eq!(records[20]["create_time"], "2024-03-18 20:00:00");
+}
+
+#[tokio::test]
+async fn order_list_all_pages_without_total() {
+    let (server, client) = stub().await;
+    //部分账号的列表接口不返回total，只能靠不满一页判断结束
+    for page in 0..2 {
+        let count = if page == 0 { ORDER_PAGE_SIZE } else { 5 };
+        let list: Vec<_> = (0..count)
+            .map(|i| order_json(&(page * ORDER_PAGE_SIZE + i).to_string(), "已完成"))
+            .collect();
+        Mock::given(path("/api/ticket/order/list"))
+            .and(query_param("page", page.to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({"list": list}))))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+
+    let orders = client.order_list_all().await.unwrap();
+    assert_eq!(orders.list.len(), 25);
+    assert_eq!(orders.total, 25);
+    assert_eq!(orders.list[24].order_id, "24");
+}
+
+#[tokio::test]
+async fn order_list_all_stops_on_empty_page_and_reports_errors() {
+    let (server, client) = stub().await;
+    let full_page: Vec<_> = (0..ORDER_PAGE_SIZE)
+        .map(|id| order_json(&id.to_string(), "已完成"))
+        .collect();
+    Mock::given(path("/api/ticket/order/list"))
+        .and(query_param("page", "0"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({
+            "total": 30,
+            "list": full_page
+        }))))
+        .mount(&server)
+        .await;
+    Mock::given(path("/api/ticket/order/list"))
+        .and(query_param("page", "1"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(show_ok(json!({"total": 30, "list": []}))),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let orders = client.order_list_all().await.unwrap();
+    assert_eq!(orders.list.len(), ORDER_PAGE_SIZE as usize);
+    assert_eq!(orders.total, 30);
+
+    server.reset().await;
+    Mock::given(path("/api/ticket/order/list"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+    assert!(matches!(
+        client.order_list_all().await,
+        Err(BiliError::Status(_))
+    ));
+}
+
+#[tokio::test]
+async fn cancel_order_succeeds() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/order/cancel"))
+        .and(query_param("order_id", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!(null))))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client.cancel_order("1").await.unwrap();
+}
+
+#[tokio::test]
+async fn cancel_order_reports_server_error() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/order/cancel"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_err(-1, "订单不存在")))
+        .mount(&server)
+        .await;
+
+    assert!(matches!(
+        client.cancel_order("404").await,
+        Err(BiliError::Server { code: -1, .. })
+    ));
+}
+
+#[tokio::test]
+async fn pay_param_returns_code_url() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/order/getPayParam"))
+        .and(query_param("order_id", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({
+            "code_url": "weixin://wxpay/bizpayurl?pr=abc&x=1"
+        }))))
+        .mount(&server)
+        .await;
+
+    assert_eq!(
+        client.pay_param("1").await.unwrap(),
+        "weixin://wxpay/bizpayurl?pr=abc&x=1"
+    );
+}
+
+#[tokio::test]
+async fn pay_param_reports_missing_code_url_and_server_error() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/order/getPayParam"))
+        .and(query_param("order_id", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({}))))
+        .mount(&server)
+        .await;
+    Mock::given(path("/api/ticket/order/getPayParam"))
+        .and(query_param("order_id", "2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_err(-1, "订单已失效")))
+        .mount(&server)
+        .await;
+
+    assert!(matches!(
+        client.pay_param("1").await,
+        Err(BiliError::Decode(_))
+    ));
+    assert!(matches!(
+        client.pay_param("2").await,
+        Err(BiliError::Server { code: -1, .. })
+    ));
+}
+
+#[tokio::test]
+async fn buyer_info_exposes_buyers_through_accessors() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/buyer/list"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({
+            "list": [buyer_json(1, "张三", 1), buyer_json(2, "李四", 0)]
+        }))))
+        .mount(&server)
+        .await;
+
+    let buyers = client.buyer_info().await.unwrap();
+    assert_eq!(buyers.len(), 2);
+    assert_eq!(buyers[0].name(), "张三");
+    assert!(buyers[0].is_default());
+    assert!(!buyers[1].is_default());
+    assert_eq!(buyers[1].id(), 2);
+}
+
+#[tokio::test]
+async fn buyer_create_and_edit_send_form() {
+    let (server, client) = stub().await;
+    Mock::given(method("POST"))
+        .and(path("/api/ticket/buyer/create"))
+        .and(body_string_contains("tel=13800000000"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({}))))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/ticket/buyer/edit"))
+        .and(body_string_contains("id=2"))
+        .and(body_string_contains("is_default=1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({}))))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let form = BuyerForm {
+        name: "王五".to_string(),
+        personal_id: "110101199001010011".to_string(),
+        tel: "13800000000".to_string(),
+        ..Default::default()
+    };
+    client.buyer_create(&form).await.unwrap();
+    let buyer: Buyer = serde_json::from_value(buyer_json(2, "李四", 0)).unwrap();
+    client.buyer_set_default(&buyer).await.unwrap();
+}
+
+#[tokio::test]
+async fn buyer_delete_and_create_report_server_error() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/buyer/delete"))
+        .and(body_string_contains("id=3"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!(null))))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(path("/api/ticket/buyer/create"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(show_err(100003, "身份证号码不正确")),
+        )
+        .mount(&server)
+        .await;
+
+    client.buyer_delete(3).await.unwrap();
+    assert!(matches!(
+        client.buyer_create(&BuyerForm::default()).await,
+        Err(BiliError::Server { code: 100003, .. })
+    ));
+}
+
+#[tokio::test]
+async fn address_list_decodes_addresses() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/addr/list"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({
+            "list": [address_json(7, 1), address_json(8, 0)]
+        }))))
+        .mount(&server)
+        .await;
+
+    let addresses = client.address_list().await.unwrap();
+    assert_eq!(addresses.len(), 2);
+    assert_eq!(
+        addresses[0].full_address(),
+        "上海市上海市浦东新区世纪大道1号"
+    );
+    assert_eq!(addresses[1].def, 0);
+}
+
+#[tokio::test]
+async fn address_add_and_edit_send_form() {
+    let (server, client) = stub().await;
+    Mock::given(method("POST"))
+        .and(path("/api/ticket/addr/add"))
+        .and(body_string_contains("phone=13800000000"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({"id": 9}))))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/ticket/addr/edit"))
+        .and(body_string_contains("id=7"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_err(-400, "地址不存在")))
+        .mount(&server)
+        .await;
+
+    let mut address: Address = serde_json::from_value(address_json(0, 0)).unwrap();
+    client.address_add(&address).await.unwrap();
+    address.id = 7;
+    assert!(matches!(
+        client.address_edit(&address).await,
+        Err(BiliError::Server { code: -400, .. })
+    ));
+}
+
+#[tokio::test]
+async fn wait_order_created_polls_until_final() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/order/createstatus"))
+        .and(query_param("orderId", "1"))
+        .and(query_param("token", "prepare-token"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(show_err(ORDER_CREATING_ERRNO, "订单创建中")),
+        )
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(path("/api/ticket/order/createstatus"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({
+            "order_status": 1,
+            "pay_remain_time": 899
+        }))))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let status = client
+        .wait_order_created(77777, "prepare-token", 1, Duration::from_millis(10), 5)
+        .await
+        .unwrap();
+    assert_eq!(status.pay_remain_time, 899);
+}
+
+#[tokio::test]
+async fn wait_order_created_keeps_polling_pending_status() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/order/createstatus"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({
+            "order_status": 0,
+            "pay_remain_time": 0
+        }))))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(path("/api/ticket/order/createstatus"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({
+            "order_status": 1,
+            "pay_remain_time": 900
+        }))))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let status = client
+        .wait_order_created(77777, "t", 1, Duration::from_millis(10), 5)
+        .await
+        .unwrap();
+    assert_eq!(status.order_status, OrderStatus::Unpaid);
+    assert_eq!(status.pay_remain_time, 900);
+
+    server.reset().await;
+    Mock::given(path("/api/ticket/order/createstatus"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({
+            "order_status": 0
+        }))))
+        .expect(3)
+        .mount(&server)
+        .await;
+    assert!(matches!(
+        client
+            .wait_order_created(77777, "t", 1, Duration::from_millis(10), 3)
+            .await,
+        Err(BiliError::Timeout(_))
+    ));
+}
+
+#[tokio::test]
+async fn wait_order_created_reports_failure_and_timeout() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/order/createstatus"))
+        .and(query_param("orderId", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_err(100009, "库存不足")))
+        .mount(&server)
+        .await;
+    Mock::given(path("/api/ticket/order/createstatus"))
+        .and(query_param("orderId", "2"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(show_err(ORDER_CREATING_ERRNO, "订单创建中")),
+        )
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    assert!(matches!(
+        client
+            .wait_order_created(77777, "t", 1, Duration::from_millis(10), 3)
+            .await,
+        Err(BiliError::Server { code: 100009, .. })
+    ));
+    assert!(matches!(
+        client
+            .wait_order_created(77777, "t", 2, Duration::from_millis(10), 3)
+            .await,
+        Err(BiliError::Timeout(_))
+    ));
+}
+
+#[tokio::test]
+async fn wait_order_created_rejects_cancelled_order() {
+    let (server, client) = stub().await;
+    Mock::given(path("/api/ticket/order/createstatus"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(show_ok(json!({
+            "order_status": 4,
+            "pay_remain_time": 0
+        }))))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    match client
+        .wait_order_created(77777, "t", 1, Duration::from_millis(10), 3)
+        .await
+    {
+        Err(BiliError::Server { code, msg }) => {
+            assert_eq!(code, OrderStatus::Cancelled.code() as i64);
+            assert_eq!(msg, "订单已被取消");
+        }
+        _ => panic!("expected server error"),
+    }
+}
+
+async fn mount_nav(server: &wiremock::MockServer, body: serde_json::Value) {
+    Mock::given(path("/x/web-interface/nav"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(body))
+        .mount(server)
+        .await;
+}
+
+async fn mount_cookie_info(server: &wiremock::MockServer, refresh: bool) {
+    Mock::given(path("/x/passport-login/web/cookie/info"))
+        .and(query_param("csrf", "csrf"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(passport_ok(json!({
+            "refresh": refresh,
+            "timestamp": 1710763200000i64
+        }))))
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn session_health_reports_valid_and_needs_refresh() {
+    let (server, client) = stub().await;
+    mount_nav(
+        &server,
+        passport_ok(json!({"isLogin": true, "uname": "user", "face": ""})),
+    )
+    .await;
+    mount_cookie_info(&server, false).await;
+    assert_eq!(client.session_health().await.unwrap(), SessionHealth::Valid);
+
+    let (server, client) = stub().await;
+    mount_nav(&server, passport_ok(json!({"isLogin": true}))).await;
+    mount_cookie_info(&server, true).await;
+    assert_eq!(
+        client.session_health().await.unwrap(),
+        SessionHealth::NeedsRefresh
+    );
+
+    //没有bili_jct时不请求cookie/info
+    let (server, client) = stub().await;
+    client.set_cookie("SESSDATA=sess");
+    mount_nav(&server, passport_ok(json!({"isLogin": true}))).await;
+    assert_eq!(
+        client.session_health().await.unwrap(),
+        SessionHealth::ValidNoRefresh
+    );
+    assert_eq!(server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn session_health_reports_expired_and_http_error() {
+    let (server, client) = stub().await;
+    mount_nav(
+        &server,
+        json!({"code": NOT_LOGIN_CODE, "message": "账号未登录", "data": {"isLogin": false}}),
+    )
+    .await;
+    assert_eq!(
+        client.session_health().await.unwrap(),
+        SessionHealth::Expired
+    );
+
+    let (server, client) = stub().await;
+    Mock::given(path("/x/web-interface/nav"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+    assert!(matches!(
+        client.session_health().await,
+        Err(BiliError::Status(status)) if status.as_u16() == 503
+    ));
+}
+
+#[tokio::test]
+async fn refresh_cookie_replaces_cookies_and_confirms() {
+    let (server, client) = stub().await;
+    mount_cookie_info(&server, true).await;
+    Mock::given(path_regex("^/correspond/1/[0-9a-f]{256}$"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("<html><div id=\"1-name\">refresh_csrf</div></html>"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/x/passport-login/web/cookie/refresh"))
+        .and(body_string_contains("csrf=csrf"))
+        .and(body_string_contains("refresh_csrf=refresh_csrf"))
+        .and(body_string_contains("refresh_token=old_token"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .append_header("Set-Cookie", "SESSDATA=new_sess; Path=/; HttpOnly")
+                .append_header("Set-Cookie", "bili_jct=new_csrf; Path=/")
+                .set_body_json(passport_ok(json!({
+                    "status": 0,
+                    "message": "",
+                    "refresh_token": "new_token"
+                }))),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/x/passport-login/web/confirm/refresh"))
+        .and(body_string_contains("csrf=new_csrf"))
+        .and(body_string_contains("refresh_token=old_token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(passport_ok(json!(null))))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let token = client.refresh_cookie("old_token").await.unwrap();
+    assert_eq!(token, "new_token");
+    assert_eq!(client.cookie(), "SESSDATA=new_sess; bili_jct=new_csrf");
+}
+
+#[tokio::test]
+async fn refresh_cookie_reports_invalid_refresh_token() {
+    let (server, client) = stub().await;
+    mount_cookie_info(&server, true).await;
+    Mock::given(path_regex("^/correspond/1/"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_string("<div id=\"1-name\">refresh_csrf</div>"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(path("/x/passport-login/web/cookie/refresh"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(passport_err(86095, "refresh_csrf 错误")),
+        )
+        .mount(&server)
+        .await;
+
+    assert!(matches!(
+        client.refresh_cookie("old_token").await,
+        Err(BiliError::Server { code: 86095, .. })
+    ));
+    assert_eq!(client.cookie(), "SESSDATA=sess; bili_jct=csrf");
+}
+
+#[tokio::test]
+async fn server_time_returns_now_and_errors() {
+    let (server, client) = stub().await;
+    Mock::given(path("/x/report/click/now"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(passport_ok(json!({"now": 1710763200}))),
+        )
+        .mount(&server)
+        .await;
+    assert_eq!(client.server_time().await.unwrap(), 1710763200);
+
+    let (server, client) = stub().await;
+    Mock::given(path("/x/report/click/now"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(passport_err(-412, "请求被拦截")))
+        .mount(&server)
+        .await;
+    assert!(matches!(
+        client.server_time().await,
+        Err(BiliError::Server { code: -412, .. })
+    ));
+}
+
+#[tokio::test]
+async fn responses_calibrate_server_clock() {
+    let (server, client) = stub().await;
+    Mock::given(path("/x/web-interface/nav"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Date", "Mon, 18 Mar 2024 12:00:00 GMT")
+                .set_body_json(passport_ok(json!({"isLogin": true}))),
+        )
+        .mount(&server)
+        .await;
+    assert!(!client.clock().is_synced());
+    client.nav_info().await.unwrap();
+    let clock = client.clock();
+    assert!(clock.is_synced());
+    let server_now = clock.now_millis();
+    assert!((1710763200000..1710763202000).contains(&server_now));
+}
+
+#[tokio::test]
+async fn sync_clock_samples_server_time() {
+    let (server, client) = stub().await;
+    let now = bili_lib::local_millis() / 1000 + 3600;
+    Mock::given(path("/x/report/click/now"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(passport_ok(json!({"now": now}))))
+        .expect(3)
+        .mount(&server)
+        .await;
+    let offset = client.sync_clock(3).await.unwrap();
+    //stub的Date头是真实时间，和伪造的服务器时间矛盾，只采用服务器时间接口的样本
+    assert!((3_598_000..=3_601_000).contains(&offset));
+}
@@ -0,0 +1,34 @@
+#![allow(dead_code)]
+
+pub use bili_lib::fixture::*;
+use bili_lib::{BaseUrls, BiliClient};
+use serde_json::{json, Value};
+use wiremock::MockServer;
+
+pub async fn stub() -> (MockServer, BiliClient) {
+    let server = MockServer::start().await;
+    let client = BiliClient::new(BaseUrls {
+        show: server.uri(),
+        api: server.uri(),
+        passport: server.uri(),
+        www: server.uri(),
+    });
+    client.set_cookie("SESSDATA=sess; bili_jct=csrf");
+    (server, client)
+}
+
+pub fn show_ok(data: Value) -> Value {
+    json!({"errno": 0, "msg": "", "data": data})
+}
+
+pub fn show_err(errno: i64, msg: &str) -> Value {
+    json!({"errno": errno, "msg": msg, "data": {}})
+}
+
+pub fn passport_ok(data: Value) -> Value {
+    json!({"code": 0, "message": "0", "ttl": 1, "data": data})
+}
+
+pub fn passport_err(code: i64, message: &str) -> Value {
+    json!({"code": code, "message": message, "ttl": 1})
+}
@@ -1,15 +1,28 @@
-use crate::task::load_config;
-use bili_lib::{Order, PrepareForm, Project, Ticket};
+use crate::credential::take_plaintext_cookie;
+use crate::log::{LogLevel, LogSource, Logger};
+use crate::profile::{
+    current_profile, list_profiles, migrate_legacy_files, set_current_profile, DEFAULT_PROFILE,
+    PROFILE_ROOT,
+};
+use crate::qr::QrTexture;
+use crate::scheduler::{format_countdown, format_pay_remain, PaymentReminder, SaleSchedule};
+use crate::settings::{migrate_settings, Settings};
+use crate::watch::WATCH_MIN_INTERVAL;
+use bili_lib::{
+    is_passphrase_protected, load_credentials, yuan, Address, BiliClient, Buyer, BuyerForm,
+    ExportFormat, Order, Project, Ticket,
+};
 use eframe::egui::{vec2, FontData, FontFamily, Image, Vec2};
 use eframe::{egui, App, CreationContext};
 use egui_extras::install_image_loaders;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
-use std::io::{Read, Write};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub enum OrderType {
@@ -27,58 +40,87 @@ pub struct NamePhoneForm {
 
 pub struct BiliTicket {
     pub runtime: tokio::runtime::Runtime,
-    pub terminal_buffer: Arc<Mutex<String>>,
+    pub logger: Logger,
+    pub log_level_filter: LogLevel,
+    pub log_keyword: String,
     pub show_login_qr: bool,
     pub login_qr_url: String,
-    pub config: Config,
+    pub login_qr: QrTexture,
+    pub pay_qr: QrTexture,
+    pub settings: Settings,
+    pub state: AppState,
     pub logging: Arc<AtomicBool>,
-    pub client: Arc<Client>,
-    pub blocking_client: Arc<reqwest::blocking::Client>,
+    pub client: Arc<BiliClient>,
     pub handler_order: Arc<AtomicBool>,
+    pub handler_session: Arc<AtomicBool>,
+    pub credentials_refreshed: Arc<AtomicBool>, //后台刷新了cookie，等界面线程保存
+    pub orders_total: Arc<AtomicUsize>,
+    pub order_next_page: u32,
+    pub first_page_full: Arc<AtomicBool>, //第一页是满的，可能还有更早的订单
+    pub last_page_full: bool,             //最后加载的更早一页是满的
+    pub show_buyer_window: bool,
+    pub buyers: Vec<Buyer>,
+    pub buyer_form: BuyerForm,
+    pub addresses: Vec<Address>,
+    pub address_form: Address,
+    pub pay_deadline: Option<i64>, //付款码对应订单的支付截止时间，服务器时间的毫秒时间戳
+    pub pay_reminder: PaymentReminder,
+    pub credential_locked: bool,
+    pub credential_unreadable: bool, //凭据文件解密失败，重新登录前不覆盖
+    pub show_credential_window: bool,
+    pub passphrase_input: String,
+    pub passphrase: Option<String>,
+    pub profile: String,
+    pub profiles: Vec<String>,
+    pub profile_name_input: String,
+    pub sale_schedule: Arc<Mutex<Option<SaleSchedule>>>,
+    pub handler_sale: Arc<AtomicBool>,
+    pub sale_order: Arc<Mutex<Option<u64>>>, //开售自动购票生成的订单，等界面显示付款码
+    pub handler_watch: Arc<AtomicBool>,
+    pub watch_snapshots: Arc<Mutex<HashMap<u64, Project>>>,
+    pub watch_alerts: Arc<Mutex<Vec<String>>>,
+    pub watch_alerts_seen: usize,
+    pub show_watch_window: bool,
+    pub watch_input: String,
+    pub order_detail: Option<Order>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Config {
-    ticket_count: String,
-    pub name_phone_form: NamePhoneForm,
-    loaded_user_head: bool,
+//运行时状态，不保存到文件
+pub struct AppState {
+    pub loaded_user_head: bool,
     pub order_type: OrderType,
-    select_order_id: String,
-    is_select_ticket: bool,
+    pub select_order_id: String,
+    pub is_select_ticket: bool,
     pub ticket: Ticket,
-    screen_id: i64,
-    is_got_project: bool,
-    project_image_url: String,
+    pub is_got_project: bool,
+    pub project_image_url: String,
     pub show_paying_qr: bool,
     pub project: Option<Project>,
-    pub target_project: String,
     pub user_name: String,
     pub user_head_img_url: String,
     pub orders: Arc<Mutex<Vec<Order>>>,
     pub cookie: Arc<Mutex<String>>,
+    pub refresh_token: Arc<Mutex<String>>,
     pub is_login: Arc<AtomicBool>,
     pub pay_code: String,
 }
 
-impl Default for Config {
+impl Default for AppState {
     fn default() -> Self {
-        Config {
-            ticket_count: String::from("1"),
-            name_phone_form: NamePhoneForm::default(),
+        AppState {
             loaded_user_head: false,
             order_type: OrderType::Anonymous,
             select_order_id: String::default(),
             is_select_ticket: false,
             ticket: Ticket::default(),
-            screen_id: 0,
             is_got_project: false,
             project_image_url: String::default(),
             project: None,
-            target_project: String::default(),
             user_name: String::default(),
             user_head_img_url: String::default(),
             orders: Arc::new(Mutex::new(vec![])),
             cookie: Arc::new(Mutex::new(String::default())),
+            refresh_token: Arc::new(Mutex::new(String::default())),
             is_login: Arc::new(AtomicBool::new(false)),
             pay_code: String::default(),
             show_paying_qr: false,
@@ -90,16 +132,51 @@ impl Default for BiliTicket {
     fn default() -> Self {
         BiliTicket {
             handler_order: Arc::new(AtomicBool::new(false)),
-            blocking_client: Arc::new(reqwest::blocking::Client::new()),
-            client: Arc::new(Client::new()),
+            handler_session: Arc::new(AtomicBool::new(false)),
+            credentials_refreshed: Arc::new(AtomicBool::new(false)),
+            orders_total: Arc::new(AtomicUsize::new(0)),
+            order_next_page: 1,
+            first_page_full: Arc::new(AtomicBool::new(false)),
+            last_page_full: false,
+            show_buyer_window: false,
+            buyers: vec![],
+            buyer_form: BuyerForm::default(),
+            addresses: vec![],
+            address_form: Address::default(),
+            pay_deadline: None,
+            pay_reminder: PaymentReminder::default(),
+            credential_locked: false,
+            credential_unreadable: false,
+            show_credential_window: false,
+            passphrase_input: String::default(),
+            passphrase: None,
+            profile: DEFAULT_PROFILE.to_string(),
+            profiles: vec![],
+            profile_name_input: String::default(),
+            sale_schedule: Arc::new(Mutex::new(None)),
+            handler_sale: Arc::new(AtomicBool::new(false)),
+            sale_order: Arc::new(Mutex::new(None)),
+            handler_watch: Arc::new(AtomicBool::new(false)),
+            watch_snapshots: Arc::new(Mutex::new(HashMap::new())),
+            watch_alerts: Arc::new(Mutex::new(vec![])),
+            watch_alerts_seen: 0,
+            show_watch_window: false,
+            watch_input: String::default(),
+            order_detail: None,
+            client: Arc::new(BiliClient::default()),
             runtime: tokio::runtime::Builder::new_multi_thread()
                 .enable_all()
                 .build()
                 .unwrap(),
-            terminal_buffer: Arc::new(Mutex::new(String::default())),
+            logger: Logger::default(),
+            log_level_filter: LogLevel::Info,
+            log_keyword: String::default(),
             show_login_qr: false,
             login_qr_url: String::default(),
-            config: load_config(),
+            login_qr: QrTexture::default(),
+            pay_qr: QrTexture::default(),
+            settings: Settings::default(),
+            state: AppState::default(),
             logging: Arc::new(AtomicBool::new(false)),
         }
     }
@@ -126,15 +203,104 @@ impl BiliTicket {
         cc.egui_ctx.set_fonts(fonts);
         let mut bili_ticket = Self::default();
 
-        bili_ticket.first_loading();
+        let root = Path::new(PROFILE_ROOT);
+        match migrate_legacy_files(root, Path::new(".")) {
+            Ok(true) => bili_ticket.log(
+                LogLevel::Info,
+                LogSource::System,
+                format!("已将旧版本的配置迁移到账户{}", DEFAULT_PROFILE).as_str(),
+            ),
+            Ok(false) => {}
+            Err(e) => bili_ticket.log(
+                LogLevel::Error,
+                LogSource::System,
+                format!("迁移旧版本配置失败，错误信息: {}", e).as_str(),
+            ),
+        }
+        bili_ticket.profile = current_profile(root);
+        bili_ticket.load_profile();
 
         bili_ticket
     }
 
-    fn first_loading(&mut self) {
-        if let Ok(mut f) = File::open("./config.json") {
-            if let Ok(config) = serde_json::from_reader(f) {
-                self.config = config;
+    //读取当前账户的设置和登录凭据
+    pub fn load_profile(&mut self) {
+        let root = Path::new(PROFILE_ROOT);
+        if let Err(e) = set_current_profile(root, &self.profile) {
+            self.log(
+                LogLevel::Error,
+                LogSource::System,
+                format!("保存当前账户失败，错误信息: {}", e).as_str(),
+            );
+        }
+        self.profiles = list_profiles(root);
+        let mut plaintext_cookie = None;
+        let path = self.config_path();
+        if let Ok(f) = File::open(&path) {
+            let settings = serde_json::from_reader::<_, serde_json::Value>(f)
+                .map_err(|e| format!("设置文件格式错误: {}", e))
+                .and_then(|mut json| {
+                    plaintext_cookie = take_plaintext_cookie(&mut json);
+                    migrate_settings(json)
+                });
+            match settings {
+                Ok(settings) => self.settings = settings,
+                Err(e) => {
+                    //读不了的设置文件先备份，避免关闭时被默认设置覆盖
+                    let backup = path.with_extension("json.bak");
+                    let _ = fs::copy(&path, &backup);
+                    self.log(
+                        LogLevel::Error,
+                        LogSource::System,
+                        format!(
+                            "读取设置失败，已备份到{}并使用默认设置，错误信息: {}",
+                            backup.display(),
+                            e
+                        )
+                        .as_str(),
+                    );
+                }
+            }
+        }
+        //旧版本明文保存的cookie迁移到加密的凭据文件，并从config.json中删除
+        if let Some(cookie) = plaintext_cookie {
+            self.set_session_cookie(&cookie);
+            self.state.is_login.store(true, Ordering::Relaxed);
+            self.store_credentials();
+            self.save_settings();
+            self.log(
+                LogLevel::Info,
+                LogSource::Login,
+                "已将config.json中的登录凭据迁移到加密的凭据文件",
+            );
+            return;
+        }
+        let path = self.credential_path();
+        if is_passphrase_protected(&path) {
+            //解锁前不能使用登录态，也不能发起扫码登录覆盖凭据
+            self.credential_locked = true;
+            self.show_credential_window = true;
+            self.state.is_login.store(false, Ordering::Relaxed);
+            return;
+        }
+        match load_credentials(&path, None) {
+            Ok(Some(credentials)) => self.restore_session(&credentials),
+            Ok(None) => self.state.is_login.store(false, Ordering::Relaxed),
+            Err(e) => {
+                self.state.is_login.store(false, Ordering::Relaxed);
+                self.credential_unreadable = true;
+                let backup = path.with_extension("json.bak");
+                let _ = fs::copy(&path, &backup);
+                self.log(
+                    LogLevel::Error,
+                    LogSource::Login,
+                    format!(
+                        "读取登录凭据失败，已备份到{}，重新登录前不会覆盖凭据文件，错误信息: {}",
+                        backup.display(),
+                        e
+                    )
+                    .as_str(),
+                );
             }
         }
     }
@@ -145,11 +311,41 @@ impl BiliTicket {
             .show(ctx, |ui| {
                 egui::menu::bar(ui, |ui| {
                     ui.menu_button("账户", |ui| {
-                        if ui.button("更换账户").clicked() {
-                            self.config = Config::default();
-                            self.handler_order.store(false, Ordering::Relaxed);
+                        ui.menu_button("切换账户", |ui| {
+                            for name in self.profiles.clone() {
+                                ui.horizontal(|ui| {
+                                    if ui.radio(name == self.profile, name.as_str()).clicked() {
+                                        self.switch_profile(&name);
+                                        ui.close_menu();
+                                    }
+                                    if name != self.profile && ui.small_button("删除").clicked() {
+                                        self.remove_profile(&name);
+                                    }
+                                });
+                            }
+                            ui.separator();
+                            ui.horizontal(|ui| {
+                                ui.text_edit_singleline(&mut self.profile_name_input);
+                                if ui.button("新建账户").clicked() {
+                                    self.new_profile();
+                                    ui.close_menu();
+                                }
+                            });
+                        });
+                        if ui.button("凭据密码").clicked() {
+                            self.show_credential_window = true;
+                            ui.close_menu();
+                        }
+                        if ui.button("购票人管理").clicked() {
+                            self.show_buyer_window = true;
+                            self.load_buyers();
+                            ui.close_menu();
                         }
                     });
+                    if ui.button("关注列表").clicked() {
+                        self.show_watch_window = true;
+                    }
+                    ui.label(format!("当前账户: {}", self.profile));
                 });
             });
     }
@@ -157,51 +353,56 @@ impl BiliTicket {
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.vertical(|ui| {
-                    if self.config.is_login.load(Ordering::Relaxed) == true {
+                    if self.state.is_login.load(Ordering::Relaxed) {
                         ui.horizontal_wrapped(|ui| {
                             ui.label("请输入票品id");
-                            ui.text_edit_singleline(&mut self.config.target_project);
+                            ui.text_edit_singleline(&mut self.settings.target_project);
                             if ui.button("确认").clicked() {
-                                self.config.is_select_ticket = false;
-                                self.print_terminal("加载票品信息...\n");
-                                let mut flag = true;
-                                self.get_project().unwrap_or_else(|_| {
-                                    self.print_terminal("载入票品信息失败,可能是票品不存在\n");
-                                    flag = false;
-                                });
-
-                                if flag {
-                                    ctx.forget_image(&self.config.project_image_url);
-                                    self.config.project_image_url =
-                                        self.config.project.clone().unwrap().performance_image;
-                                    self.config.is_got_project = true;
-                                    self.print_terminal("载入商品信息完成\n");
+                                self.state.is_select_ticket = false;
+                                self.log(LogLevel::Info, LogSource::Project, "加载票品信息...");
+                                if let Err(e) = self.get_project() {
+                                    self.log(
+                                        LogLevel::Error,
+                                        LogSource::Project,
+                                        format!("载入票品信息失败，错误信息: {}", e).as_str(),
+                                    );
+                                } else {
+                                    ctx.forget_image(&self.state.project_image_url);
+                                    self.state.project_image_url =
+                                        self.state.project.clone().unwrap().performance_image;
+                                    self.state.is_got_project = true;
+                                    self.restore_ticket_selection();
+                                    self.log(
+                                        LogLevel::Info,
+                                        LogSource::Project,
+                                        "载入商品信息完成",
+                                    );
                                 }
                             }
                         });
                     }
-                    if self.config.is_got_project {
+                    if self.state.is_got_project {
                         ui.add(
-                            Image::from_uri(self.config.project_image_url.clone())
+                            Image::from_uri(self.state.project_image_url.clone())
                                 .fit_to_exact_size(Vec2::new(405.0, 720.0)),
                         );
                     }
                 });
                 ui.vertical(|ui| {
-                    if self.config.is_got_project {
+                    if self.state.is_got_project {
                         let mut ticket_list: Vec<Ticket> = vec![];
                         ui.horizontal(|ui| {
-                            for screen in self.config.project.clone().unwrap().screen_list {
+                            for screen in self.state.project.clone().unwrap().screen_list {
                                 let mut but = egui::Button::new(screen.name.clone());
-                                if self.config.screen_id == screen.id {
+                                if self.settings.screen_id == screen.id {
                                     but = but.selected(true);
                                 }
 
                                 if ui.add(but).clicked() {
-                                    self.config.screen_id = screen.id;
+                                    self.settings.screen_id = screen.id;
                                 }
 
-                                if screen.id == self.config.screen_id {
+                                if screen.id == self.settings.screen_id {
                                     ticket_list = screen.ticket_list;
                                 }
                             }
@@ -209,117 +410,291 @@ impl BiliTicket {
                         ui.horizontal(|ui| {
                             for ticket in ticket_list {
                                 let mut but = egui::Button::new(ticket.desc.clone());
-                                if self.config.ticket.id == ticket.id {
+                                if self.state.ticket.id == ticket.id {
                                     but = but.selected(true);
                                 }
                                 if ui.add(but).clicked() {
-                                    self.config.ticket = ticket;
-                                    self.config.is_select_ticket = true;
+                                    self.settings.ticket_id = ticket.id;
+                                    self.state.ticket = ticket;
+                                    self.state.is_select_ticket = true;
                                 }
                             }
                         });
 
-                        if self.config.is_select_ticket {
-                            match self.config.order_type {
+                        if self.state.is_select_ticket {
+                            match self.state.order_type {
                                 OrderType::NamePhone => {
-                                    ui.horizontal(|ui| {
-                                        ui.vertical(|ui| {
-                                            ui.label("姓名");
-                                            ui.text_edit_singleline(
-                                                &mut self.config.name_phone_form.name,
-                                            );
-                                        });
-                                        ui.vertical(|ui| {
-                                            ui.label("手机号");
-                                            ui.text_edit_singleline(
-                                                &mut self.config.name_phone_form.phone,
-                                            );
-                                        });
-                                    });
-                                    ui.horizontal(|ui| {
-                                        ui.label("购买数量");
-                                        if ui.button("-").clicked() {
-                                            self.config.ticket_count =
-                                                (self.config.ticket_count.parse::<u8>().unwrap()
-                                                    - 1)
-                                                .to_string();
-                                        }
-                                        ui.add_sized(
-                                            vec2(100.0, 20.0),
-                                            egui::TextEdit::singleline(
-                                                &mut self.config.ticket_count,
-                                            ),
-                                        );
-                                        if ui.button("+").clicked() {
-                                            self.config.ticket_count =
-                                                (self.config.ticket_count.parse::<u8>().unwrap()
-                                                    + 1)
-                                                .to_string();
-                                        }
-                                    });
-                                    ui.horizontal(|ui| {
-                                        if ui.button("立即购票").clicked() {
-                                            if self.config.ticket_count.parse().unwrap() == 0 {
-                                                self.print_terminal("购买数量不能为0\n");
-                                            } else {
-                                                let prepare_form = PrepareForm {
-                                                    project_id: self
-                                                        .config
-                                                        .target_project
-                                                        .parse()
-                                                        .unwrap(),
-                                                    screen_id: self.config.screen_id,
-                                                    order_type: 1,
-                                                    count: self.config.ticket_count.parse().unwrap(),
-                                                    sku_id: self.config.ticket.id,
-                                                };
-                                                self.buy_ticket_now(
-                                                    &prepare_form,
-                                                    &self.config.name_phone_form,
-                                                );
-                                            }
-                                        }
-                                    });
+                                    self.ui_name_phone(ui);
+                                    self.ui_ticket_count(ui);
+                                    self.ui_buy_button(ui);
+                                }
+                                OrderType::Buyer => {
+                                    self.ui_buyer_picker(ui);
+                                    self.ui_ticket_count(ui);
+                                    self.ui_buy_button(ui);
+                                }
+                                OrderType::Deliver => {
+                                    self.ui_name_phone(ui);
+                                    self.ui_address_picker(ui);
+                                    let express_fee = self.express_fee();
+                                    if express_fee > 0 {
+                                        ui.label(format!(
+                                            "运费: {:.2}元",
+                                            express_fee as f64 / 100.0
+                                        ));
+                                    }
+                                    self.ui_ticket_count(ui);
+                                    self.ui_buy_button(ui);
+                                }
+                                OrderType::Anonymous => {
+                                    ui.label("该票品支持匿名购买，无需填写购票人信息");
+                                    self.ui_ticket_count(ui);
+                                    self.ui_buy_button(ui);
                                 }
-                                OrderType::Buyer => {}
-                                OrderType::Deliver => {}
-                                OrderType::Anonymous => {}
                             }
+                            self.ui_sale_schedule(ui);
                         }
                     }
                 });
             });
         });
     }
+    fn ui_sale_schedule(&mut self, ui: &mut egui::Ui) {
+        ui.separator();
+        if self.state.ticket.sale_start.is_empty() {
+            return;
+        }
+        ui.label(format!("开售时间: {}", self.state.ticket.sale_start));
+        let schedule = self.sale_schedule.lock().unwrap().as_ref().map(|schedule| {
+            (
+                schedule.sku_id,
+                schedule.sale_start,
+                schedule.is_opened(),
+                schedule.auto_buy,
+            )
+        });
+        if schedule.is_some_and(|(sku_id, ..)| sku_id != self.state.ticket.id) {
+            self.stop_sale_schedule();
+            self.log(
+                LogLevel::Warn,
+                LogSource::Project,
+                "已切换票档，开售倒计时已取消",
+            );
+            return;
+        }
+        let Some((_, sale_start, opened, auto_buy)) = schedule else {
+            ui.horizontal(|ui| {
+                ui.label("提前提醒(秒)");
+                ui.add(egui::DragValue::new(&mut self.settings.remind_before_secs));
+            });
+            ui.checkbox(
+                &mut self.settings.auto_buy_at_open,
+                "开售时自动尝试购买一次",
+            );
+            if ui.button("开始倒计时").clicked() {
+                self.start_sale_schedule(ui.ctx());
+            }
+            return;
+        };
+        ui.horizontal(|ui| {
+            if opened {
+                ui.label("已开售");
+            } else {
+                let remaining = sale_start - self.client.clock().now_millis();
+                ui.label(format!("距离开售还有 {}", format_countdown(remaining)));
+                ui.ctx().request_repaint_after(Duration::from_millis(100));
+            }
+            if auto_buy {
+                ui.label("开售时自动购票");
+            }
+            if ui.button("取消倒计时").clicked() {
+                self.stop_sale_schedule();
+            }
+        });
+    }
+    fn ui_name_phone(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.vertical(|ui| {
+                ui.label("姓名");
+                ui.text_edit_singleline(&mut self.settings.name_phone_form.name);
+            });
+            ui.vertical(|ui| {
+                ui.label("手机号");
+                ui.text_edit_singleline(&mut self.settings.name_phone_form.phone);
+            });
+        });
+    }
+    fn ui_address_picker(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("收货地址");
+            if ui.link("刷新").clicked() {
+                self.load_addresses();
+            }
+        });
+        for address in self.addresses.clone() {
+            ui.horizontal(|ui| {
+                ui.radio_value(
+                    &mut self.settings.selected_address,
+                    address.id,
+                    format!(
+                        "{} {} {}",
+                        address.name,
+                        address.phone,
+                        address.full_address()
+                    ),
+                );
+                if ui.link("编辑").clicked() {
+                    self.address_form = address.clone();
+                }
+            });
+        }
+        egui::CollapsingHeader::new(if self.address_form.id == 0 {
+            "新增收货地址"
+        } else {
+            "编辑收货地址"
+        })
+        .show(ui, |ui| {
+            egui::Grid::new("address form").show(ui, |ui| {
+                ui.label("收件人");
+                ui.text_edit_singleline(&mut self.address_form.name);
+                ui.end_row();
+                ui.label("手机号");
+                ui.text_edit_singleline(&mut self.address_form.phone);
+                ui.end_row();
+                ui.label("省份");
+                ui.text_edit_singleline(&mut self.address_form.prov);
+                ui.end_row();
+                ui.label("城市");
+                ui.text_edit_singleline(&mut self.address_form.city);
+                ui.end_row();
+                ui.label("区县");
+                ui.text_edit_singleline(&mut self.address_form.area);
+                ui.end_row();
+                ui.label("详细地址");
+                ui.text_edit_singleline(&mut self.address_form.addr);
+                ui.end_row();
+            });
+            ui.horizontal(|ui| {
+                if ui.button("保存地址").clicked() {
+                    self.save_address();
+                }
+                if ui.button("清空").clicked() {
+                    self.address_form = Address::default();
+                }
+            });
+        });
+    }
+    fn ui_buyer_picker(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("实名购票人");
+            if ui.link("刷新").clicked() {
+                self.load_buyers();
+            }
+        });
+        ui.horizontal_wrapped(|ui| {
+            for buyer in &self.buyers {
+                let mut checked = self.settings.selected_buyers.contains(&buyer.id());
+                if ui
+                    .checkbox(&mut checked, format!("{} {}", buyer.name(), buyer.tel()))
+                    .changed()
+                {
+                    if checked {
+                        self.settings.selected_buyers.push(buyer.id());
+                    } else {
+                        self.settings.selected_buyers.retain(|id| *id != buyer.id());
+                    }
+                    self.settings.ticket_count = self.settings.selected_buyers.len().to_string();
+                }
+            }
+        });
+    }
+    fn ui_ticket_count(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("购买数量");
+            if ui.button("-").clicked() {
+                self.settings.ticket_count = self
+                    .settings
+                    .ticket_count
+                    .parse::<u8>()
+                    .unwrap_or(1)
+                    .saturating_sub(1)
+                    .to_string();
+            }
+            ui.add_sized(
+                vec2(100.0, 20.0),
+                egui::TextEdit::singleline(&mut self.settings.ticket_count),
+            );
+            if ui.button("+").clicked() {
+                self.settings.ticket_count = self
+                    .settings
+                    .ticket_count
+                    .parse::<u8>()
+                    .unwrap_or(0)
+                    .saturating_add(1)
+                    .to_string();
+            }
+        });
+    }
+    fn ui_buy_button(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("立即购票").clicked() {
+                if self.settings.ticket_count.parse::<u8>().unwrap_or(0) == 0 {
+                    self.log(LogLevel::Warn, LogSource::Order, "购买数量不能为0");
+                } else {
+                    let prepare_form = self.prepare_form();
+                    self.buy_ticket_now(&prepare_form);
+                }
+            }
+        });
+    }
     fn ui_order(&mut self, ctx: &egui::Context) {
         egui::TopBottomPanel::bottom("order panel")
             .resizable(true)
             .default_height(100.0)
             .show(ctx, |ui| {
-                if self.config.is_login.load(Ordering::Relaxed) == false {
-                    if self.logging.load(Ordering::Relaxed) == false {
-                        self.do_login();
+                if !self.state.is_login.load(Ordering::Relaxed) && !self.credential_locked {
+                    //重新登录后需要重新加载用户信息
+                    self.state.loaded_user_head = false;
+                    if !self.logging.load(Ordering::Relaxed) {
+                        if let Err(e) = self.do_login() {
+                            self.log(
+                                LogLevel::Error,
+                                LogSource::Login,
+                                format!("获取登录二维码失败，错误信息: {}", e).as_str(),
+                            );
+                        }
                         self.logging.store(true, Ordering::Relaxed);
                     }
                     ctx.request_repaint();
-                    ui.add(Image::from_uri(self.login_qr_url.clone()));
+                    self.login_qr
+                        .show(ui, &self.login_qr_url, vec2(250.0, 250.0));
                 }
-                if self.config.is_login.load(Ordering::Relaxed) == true {
-                    ctx.forget_image(&self.login_qr_url);
-                    if !self.config.loaded_user_head {
-                        self.print_terminal("加载用户昵称和头像...\n");
-                        self.get_user_head();
-                        self.config.loaded_user_head = true;
+                if self.state.is_login.load(Ordering::Relaxed) {
+                    self.login_qr.clear();
+                    if !self.state.loaded_user_head {
+                        self.log(LogLevel::Info, LogSource::Login, "加载用户昵称和头像...");
+                        if let Err(e) = self.get_user_head() {
+                            self.log(
+                                LogLevel::Error,
+                                LogSource::Login,
+                                format!("加载用户信息失败，错误信息: {}", e).as_str(),
+                            );
+                        }
+                        self.state.loaded_user_head = true;
+                    }
+                    if !self.handler_session.load(Ordering::Relaxed) {
+                        self.handler_session();
+                        self.handler_session.store(true, Ordering::Relaxed);
                     }
                     if !self.handler_order.load(Ordering::Relaxed) {
-                        self.print_terminal("加载订单数据...\n");
+                        self.log(LogLevel::Info, LogSource::Order, "加载订单数据...");
                         self.handler_orders();
                         self.handler_order.store(true, Ordering::Relaxed);
                     }
 
                     egui::SidePanel::left("user_head panel").show_inside(ui, |ui| {
-                        ui.heading(self.config.user_name.clone());
-                        ui.add(Image::from_uri(self.config.user_head_img_url.clone()));
+                        ui.heading(self.state.user_name.clone());
+                        ui.add(Image::from_uri(self.state.user_head_img_url.clone()));
                     });
                     let height = ui.available_size().y;
                     egui::ScrollArea::vertical()
@@ -327,33 +702,51 @@ impl BiliTicket {
                         .show(ui, |ui| {
                             ui.horizontal(|ui| {
                                 ui.vertical(|ui| {
-                                    let orders = self.config.orders.lock().unwrap().clone();
-                                    let mut no_pay_wait = true;
+                                    let orders = self.state.orders.lock().unwrap().clone();
+                                    let loaded = orders.len();
+                                    if self.check_payment_deadlines(&orders) {
+                                        ctx.send_viewport_cmd(
+                                            egui::ViewportCommand::RequestUserAttention(
+                                                egui::UserAttentionType::Critical,
+                                            ),
+                                        );
+                                    }
+                                    let now = self.client.clock().now_millis();
+                                    if orders.iter().any(|order| order.is_unpaid()) {
+                                        ctx.request_repaint_after(Duration::from_secs(1));
+                                    }
                                     for order in orders {
                                         ui.horizontal_wrapped(|ui| {
                                             ui.label(order.item_info.name.clone());
-                                            ui.label(order.sub_status_name.clone());
-                                            if order.sub_status_name.clone() == "待支付" {
-                                                no_pay_wait = false;
-                                                if self.config.select_order_id != order.order_id {
+                                            ui.label(order.status_name());
+                                            if ui.link("详情").clicked() {
+                                                self.load_order_detail(&order.order_id);
+                                            }
+                                            if order.is_unpaid() {
+                                                if let Some(deadline) = order.pay_deadline() {
+                                                    let remaining = deadline * 1000 - now;
+                                                    if remaining > 0 {
+                                                        ui.label(format!(
+                                                            "剩余 {}",
+                                                            format_pay_remain(remaining)
+                                                        ));
+                                                    } else {
+                                                        ui.label("已超时");
+                                                    }
+                                                }
+                                                if self.state.select_order_id != order.order_id {
                                                     if ui.link("点此显示付款二维码").clicked()
                                                     {
-                                                        ctx.forget_image(&self.config.pay_code);
-                                                        self.print_terminal("请求付款二维码...\n");
-                                                        if self.do_paying(order.order_id.clone()) {
-                                                            self.config.show_paying_qr = true;
-                                                            self.config.select_order_id =
-                                                                order.order_id.clone();
-                                                        }
+                                                        self.open_payment(order.order_id.clone());
                                                     }
                                                 } else {
                                                     if ui.link("隐藏付款码").clicked() {
-                                                        ctx.forget_image(&self.config.pay_code);
-                                                        self.print_terminal("删除缓存\n");
-                                                        self.config.show_paying_qr = false;
-                                                        self.config.pay_code = String::default();
-                                                        self.config.select_order_id =
-                                                            String::default();
+                                                        self.clear_payment();
+                                                        self.log(
+                                                            LogLevel::Info,
+                                                            LogSource::Payment,
+                                                            "删除缓存",
+                                                        );
                                                     }
                                                 }
 
@@ -363,17 +756,37 @@ impl BiliTicket {
                                             }
                                         });
                                     }
-                                    if no_pay_wait {
-                                        self.config.show_paying_qr = false;
-                                        self.config.pay_code = String::default();
-                                        self.config.select_order_id = String::default();
-                                    }
+                                    let total = self.orders_total.load(Ordering::Relaxed);
+                                    ui.horizontal(|ui| {
+                                        ui.label(format!("已加载{}/{}个订单", loaded, total));
+                                        if self.has_more_orders()
+                                            && ui.link("加载更早的订单").clicked()
+                                        {
+                                            self.load_more_orders();
+                                        }
+                                        if ui.link("导出CSV").clicked() {
+                                            self.export_orders(ExportFormat::Csv);
+                                        }
+                                        if ui.link("导出JSON").clicked() {
+                                            self.export_orders(ExportFormat::Json);
+                                        }
+                                    });
                                 });
                                 ui.vertical(|ui| {
-                                    if self.config.show_paying_qr {
-                                        ui.add_sized(
+                                    if self.state.show_paying_qr {
+                                        if let Some(deadline) = self.pay_deadline {
+                                            let remaining =
+                                                deadline - self.client.clock().now_millis();
+                                            ui.label(format!(
+                                                "剩余支付时间 {}",
+                                                format_pay_remain(remaining)
+                                            ));
+                                            ctx.request_repaint_after(Duration::from_secs(1));
+                                        }
+                                        self.pay_qr.show(
+                                            ui,
+                                            &self.state.pay_code,
                                             vec2(height, height),
-                                            Image::from_uri(&self.config.pay_code),
                                         );
                                     }
                                 });
@@ -382,41 +795,336 @@ impl BiliTicket {
                 }
             });
     }
-    fn ui_terminal(&self, ctx: &egui::Context) {
+    fn ui_terminal(&mut self, ctx: &egui::Context) {
         egui::TopBottomPanel::bottom("terminal panel")
             .resizable(true)
             .default_height(100.0)
             .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("级别");
+                    egui::ComboBox::from_id_source("log level filter")
+                        .selected_text(format!("{}及以上", self.log_level_filter))
+                        .show_ui(ui, |ui| {
+                            for level in LogLevel::ALL {
+                                ui.selectable_value(
+                                    &mut self.log_level_filter,
+                                    level,
+                                    format!("{}及以上", level),
+                                );
+                            }
+                        });
+                    ui.label("关键字");
+                    ui.text_edit_singleline(&mut self.log_keyword);
+                });
+                let lines: Vec<String> = self
+                    .logger
+                    .filtered(self.log_level_filter, &self.log_keyword)
+                    .iter()
+                    .map(|event| event.to_string())
+                    .collect();
                 egui::ScrollArea::vertical()
                     .stick_to_bottom(true)
                     .show(ui, |ui| {
                         ui.add_sized(
                             ui.available_size(),
-                            egui::TextEdit::multiline(
-                                &mut self.terminal_buffer.lock().unwrap().as_str(),
-                            ),
+                            egui::TextEdit::multiline(&mut lines.join("\n").as_str()),
                         );
                     });
             });
     }
-    fn ui_argument(&self, ctx: &egui::Context) {
+    fn ui_buyer(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_buyer_window;
+        egui::Window::new("购票人管理")
+            .open(&mut open)
+            .resizable(true)
+            .show(ctx, |ui| {
+                egui::Grid::new("buyer grid").striped(true).show(ui, |ui| {
+                    ui.label("姓名");
+                    ui.label("证件号");
+                    ui.label("手机号");
+                    ui.label("");
+                    ui.end_row();
+                    for buyer in self.buyers.clone() {
+                        ui.label(buyer.name());
+                        ui.label(buyer.personal_id());
+                        ui.label(buyer.tel());
+                        ui.horizontal(|ui| {
+                            if buyer.is_default() {
+                                ui.label("默认");
+                            } else if ui.link("设为默认").clicked() {
+                                self.set_default_buyer(&buyer);
+                            }
+                            if ui.link("编辑").clicked() {
+                                self.buyer_form = BuyerForm::from(&buyer);
+                            }
+                            if ui.link("删除").clicked() {
+                                self.delete_buyer(buyer.id());
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+                if self.buyer_form.id == 0 {
+                    ui.label("新增购票人");
+                } else {
+                    ui.label("编辑购票人");
+                }
+                egui::Grid::new("buyer form").show(ui, |ui| {
+                    ui.label("姓名");
+                    ui.text_edit_singleline(&mut self.buyer_form.name);
+                    ui.end_row();
+                    ui.label("身份证号");
+                    ui.text_edit_singleline(&mut self.buyer_form.personal_id);
+                    ui.end_row();
+                    ui.label("手机号");
+                    ui.text_edit_singleline(&mut self.buyer_form.tel);
+                    ui.end_row();
+                });
+                let mut is_default = self.buyer_form.is_default == 1;
+                ui.checkbox(&mut is_default, "设为默认购票人");
+                self.buyer_form.is_default = is_default as i8;
+                ui.horizontal(|ui| {
+                    if ui.button("保存").clicked() {
+                        self.save_buyer();
+                    }
+                    if ui.button("清空").clicked() {
+                        self.buyer_form = BuyerForm::default();
+                    }
+                });
+            });
+        self.show_buyer_window &= open;
+    }
+    fn ui_credential(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_credential_window;
+        egui::Window::new("登录凭据")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if self.credential_locked {
+                    ui.label("登录凭据已用密码加密，请输入密码解锁");
+                    ui.add(egui::TextEdit::singleline(&mut self.passphrase_input).password(true));
+                    ui.horizontal(|ui| {
+                        if ui.button("解锁").clicked() {
+                            match self.unlock_credentials() {
+                                Ok(()) => {
+                                    self.log(LogLevel::Info, LogSource::Login, "凭据解锁成功")
+                                }
+                                Err(e) => self.log(
+                                    LogLevel::Error,
+                                    LogSource::Login,
+                                    format!("凭据解锁失败，错误信息: {}", e).as_str(),
+                                ),
+                            }
+                        }
+                        if ui.button("放弃凭据，重新扫码登录").clicked() {
+                            self.credential_locked = false;
+                            self.show_credential_window = false;
+                            self.passphrase_input.clear();
+                        }
+                    });
+                } else {
+                    ui.label("设置密码后，登录凭据需要输入密码才能解锁");
+                    ui.label("密码留空则使用本机派生的密钥加密");
+                    ui.add(egui::TextEdit::singleline(&mut self.passphrase_input).password(true));
+                    if ui.button("保存").clicked() {
+                        self.passphrase = Some(self.passphrase_input.clone())
+                            .filter(|passphrase| !passphrase.is_empty());
+                        self.passphrase_input.clear();
+                        self.store_credentials();
+                        self.show_credential_window = false;
+                    }
+                }
+            });
+        self.show_credential_window &= open;
+    }
+    fn ui_watch(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_watch_window;
+        egui::Window::new("关注列表")
+            .open(&mut open)
+            .resizable(true)
+            .show(ctx, |ui| {
+                let snapshots = self.watch_snapshots.lock().unwrap().clone();
+                egui::Grid::new("watch grid").striped(true).show(ui, |ui| {
+                    for project_id in self.settings.watch_projects.clone() {
+                        ui.label(project_id.to_string());
+                        match snapshots.get(&project_id) {
+                            Some(project) => {
+                                let purchasable = project
+                                    .screen_list
+                                    .iter()
+                                    .flat_map(|screen| &screen.ticket_list)
+                                    .filter(|ticket| ticket.is_purchasable())
+                                    .count();
+                                ui.label(project.name.as_str());
+                                ui.label(format!(
+                                    "{} 可购买票档{}个",
+                                    project.is_sale, purchasable
+                                ));
+                            }
+                            None => {
+                                ui.label("");
+                                ui.label("等待查询");
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            if ui.link("购买").clicked() {
+                                self.settings.target_project = project_id.to_string();
+                                self.log(
+                                    LogLevel::Info,
+                                    LogSource::Project,
+                                    format!("已填入票品id{}，请点击确认加载", project_id).as_str(),
+                                );
+                            }
+                            if ui.link("删除").clicked() {
+                                self.remove_watch_project(project_id);
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("票品id");
+                    ui.text_edit_singleline(&mut self.watch_input);
+                    if ui.button("添加").clicked() {
+                        self.add_watch_project();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("刷新间隔(秒)");
+                    let min = WATCH_MIN_INTERVAL.as_secs();
+                    let changed = ui
+                        .add(
+                            egui::DragValue::new(&mut self.settings.watch_interval_secs)
+                                .clamp_range(min..=3600),
+                        )
+                        .changed();
+                    if changed && self.handler_watch.load(Ordering::Relaxed) {
+                        self.start_watch();
+                    }
+                });
+                if self.handler_watch.load(Ordering::Relaxed) {
+                    if ui.button("停止关注").clicked() {
+                        self.handler_watch.store(false, Ordering::Relaxed);
+                    }
+                } else if ui.button("开始关注").clicked() {
+                    self.start_watch();
+                }
+            });
+        self.show_watch_window &= open;
+    }
+    //有关注的票档变为可购买时弹窗提醒，直到手动关闭
+    fn ui_watch_alert(&mut self, ctx: &egui::Context) {
+        if self.handler_watch.load(Ordering::Relaxed) {
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
+        let alerts = self.watch_alerts.lock().unwrap().clone();
+        if alerts.is_empty() {
+            return;
+        }
+        if alerts.len() > self.watch_alerts_seen {
+            self.watch_alerts_seen = alerts.len();
+            ctx.send_viewport_cmd(egui::ViewportCommand::RequestUserAttention(
+                egui::UserAttentionType::Critical,
+            ));
+        }
+        egui::Window::new("开票提醒")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                for alert in &alerts {
+                    ui.label(alert.as_str());
+                }
+                if ui.button("知道了").clicked() {
+                    self.watch_alerts.lock().unwrap().clear();
+                    self.watch_alerts_seen = 0;
+                }
+            });
+    }
+    fn ui_order_detail(&mut self, ctx: &egui::Context) {
+        let Some(order) = self.order_detail.clone() else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("订单详情")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("order detail grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        let rows = [
+                            ("订单号", order.order_id.clone()),
+                            ("票品", order.item_info.name.clone()),
+                            ("场次", order.item_info.screen_name.clone()),
+                            ("票档", order.item_info.ticket_type_name.clone()),
+                            ("数量", order.count.to_string()),
+                            ("总金额", format!("¥{}", yuan(order.total_money as i64))),
+                            ("实付金额", format!("¥{}", yuan(order.pay_money as i64))),
+                            (
+                                "运费",
+                                format!("¥{}", yuan(order.express_fee.max(0) as i64)),
+                            ),
+                            ("配送方式", order.delivery_name()),
+                            ("下单时间", order.create_time()),
+                            ("支付方式", order.pay_channel.to_string()),
+                            ("状态", order.status_name()),
+                        ];
+                        for (name, value) in rows {
+                            ui.label(name);
+                            ui.label(value);
+                            ui.end_row();
+                        }
+                    });
+            });
+        if !open {
+            self.order_detail = None;
+        }
+    }
+    fn ui_argument(&mut self, ctx: &egui::Context) {
         egui::SidePanel::right("argument panel")
             .resizable(true)
-            .show(ctx, |ui| {});
+            .show(ctx, |ui| {
+                ui.label("支付提醒(剩余秒数)");
+                let mut remove = None;
+                for (i, threshold) in self.settings.pay_warn_secs.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(threshold).clamp_range(1..=900));
+                        if ui.small_button("删除").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    self.settings.pay_warn_secs.remove(i);
+                }
+                if ui.small_button("添加提醒").clicked() {
+                    self.settings.pay_warn_secs.push(60);
+                }
+            });
     }
 }
 
 impl App for BiliTicket {
-    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.ui_menu(ctx);
         self.ui_ticket(ctx);
         self.ui_terminal(ctx);
         self.ui_argument(ctx);
         self.ui_order(ctx);
+        self.ui_buyer(ctx);
+        self.ui_credential(ctx);
+        self.ui_watch(ctx);
+        self.ui_watch_alert(ctx);
+        self.open_sale_order();
+        //旧的refresh_token刷新后已失效，立即保存新凭据，不等到关闭窗口
+        if self.credentials_refreshed.swap(false, Ordering::Relaxed) {
+            self.store_credentials();
+        }
+        self.ui_order_detail(ctx);
         if ctx.input(|i| i.viewport().close_requested()) {
-            let mut file = File::create("./config.json").unwrap();
-            let json = serde_json::to_string(&self.config).unwrap();
-            file.write_all(json.as_ref()).unwrap();
+            self.save_profile();
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,8 @@
+//旧版本把cookie明文写在config.json里，取出cookie并从配置中删除
+pub fn take_plaintext_cookie(config: &mut serde_json::Value) -> Option<String> {
+    let cookie = config.as_object_mut()?.remove("cookie")?;
+    cookie
+        .as_str()
+        .filter(|cookie| !cookie.is_empty())
+        .map(ToString::to_string)
+}
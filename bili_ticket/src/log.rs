@@ -0,0 +1,210 @@
+use bili_lib::local_millis;
+use chrono::{Local, TimeZone};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub const LOG_PATH: &str = "./logs/bili_ticket.log";
+pub const LOG_CAPACITY: usize = 1000; //终端面板最多保留的条数
+const LOG_MAX_BYTES: u64 = 1024 * 1024;
+const LOG_KEEP_FILES: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LogLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 3] = [LogLevel::Info, LogLevel::Warn, LogLevel::Error];
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogLevel::Info => "信息",
+            LogLevel::Warn => "警告",
+            LogLevel::Error => "错误",
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogSource {
+    System, //配置、账户切换等
+    Login,
+    Order,
+    Project,
+    Payment,
+}
+
+impl fmt::Display for LogSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogSource::System => "系统",
+            LogSource::Login => "登录",
+            LogSource::Order => "订单",
+            LogSource::Project => "票品",
+            LogSource::Payment => "支付",
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LogEvent {
+    pub level: LogLevel,
+    pub source: LogSource,
+    pub time: i64, //本机时钟的unix毫秒时间戳，显示时按本机时区
+    pub message: String,
+}
+
+impl fmt::Display for LogEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = Local
+            .timestamp_millis_opt(self.time)
+            .single()
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S%.3f").to_string())
+            .unwrap_or_default();
+        write!(
+            f,
+            "{} [{}][{}] {}",
+            time, self.level, self.source, self.message
+        )
+    }
+}
+
+//超过大小后把bili_ticket.log改名为bili_ticket.log.1，已有的编号依次加一，最多保留keep个
+pub struct LogFile {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl LogFile {
+    pub fn new(path: &Path, max_bytes: u64, keep: usize) -> Self {
+        LogFile {
+            path: path.to_path_buf(),
+            max_bytes,
+            keep,
+        }
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        for index in (1..self.keep).rev() {
+            let from = self.rotated(index);
+            if from.exists() {
+                fs::rename(from, self.rotated(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))
+    }
+
+    fn append(&self, line: &str) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let len = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if len > 0 && len + line.len() as u64 + 1 > self.max_bytes {
+            self.rotate()?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", line)
+    }
+}
+
+struct LogBuffer {
+    events: VecDeque<LogEvent>,
+    capacity: usize,
+    file: Option<LogFile>,
+}
+
+//界面和后台任务共用，克隆后指向同一个缓冲区
+#[derive(Clone)]
+pub struct Logger {
+    buffer: Arc<Mutex<LogBuffer>>,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Logger::new(
+            LOG_CAPACITY,
+            Some(LogFile::new(
+                Path::new(LOG_PATH),
+                LOG_MAX_BYTES,
+                LOG_KEEP_FILES,
+            )),
+        )
+    }
+}
+
+impl Logger {
+    pub fn new(capacity: usize, file: Option<LogFile>) -> Self {
+        Logger {
+            buffer: Arc::new(Mutex::new(LogBuffer {
+                events: VecDeque::with_capacity(capacity),
+                capacity,
+                file,
+            })),
+        }
+    }
+
+    pub fn log(&self, level: LogLevel, source: LogSource, message: &str) {
+        let event = LogEvent {
+            level,
+            source,
+            time: local_millis(),
+            message: message.trim_end().to_string(),
+        };
+        let mut buffer = self.buffer.lock().unwrap();
+        //写文件失败后不再写，只在终端面板提示一次
+        if let Some(Err(e)) = buffer
+            .file
+            .as_ref()
+            .map(|file| file.append(&event.to_string()))
+        {
+            buffer.file = None;
+            let warning = LogEvent {
+                level: LogLevel::Warn,
+                source: LogSource::System,
+                time: event.time,
+                message: format!("写入日志文件失败，错误信息: {}", e),
+            };
+            push_event(&mut buffer, warning);
+        }
+        push_event(&mut buffer, event);
+    }
+
+    //按最低级别和关键字过滤，关键字为空时不过滤
+    pub fn filtered(&self, min_level: LogLevel, keyword: &str) -> Vec<LogEvent> {
+        let keyword = keyword.trim();
+        self.buffer
+            .lock()
+            .unwrap()
+            .events
+            .iter()
+            .filter(|event| event.level >= min_level)
+            .filter(|event| keyword.is_empty() || event.to_string().contains(keyword))
+            .cloned()
+            .collect()
+    }
+}
+
+fn push_event(buffer: &mut LogBuffer, event: LogEvent) {
+    if buffer.events.len() == buffer.capacity {
+        buffer.events.pop_front();
+    }
+    buffer.events.push_back(event);
+}
@@ -3,14 +3,23 @@ use crate::app::BiliTicket;
 use eframe::Theme;
 
 mod app;
+mod credential;
+mod log;
+mod profile;
+mod qr;
+mod scheduler;
+mod settings;
 mod task;
 #[cfg(test)]
 mod test;
+mod watch;
 
 fn main() {
-    let mut native_options = eframe::NativeOptions::default();
-    native_options.follow_system_theme = false;
-    native_options.default_theme = Theme::Light;
+    let native_options = eframe::NativeOptions {
+        follow_system_theme: false,
+        default_theme: Theme::Light,
+        ..Default::default()
+    };
     eframe::run_native(
         "Bili_Ticket",
         native_options,
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const PROFILE_ROOT: &str = "./profiles";
+pub const DEFAULT_PROFILE: &str = "默认";
+
+//每个账户一个目录，分别保存设置和加密的登录凭据
+#[derive(Serialize, Deserialize)]
+struct ProfileIndex {
+    current: String,
+}
+
+pub fn config_path(root: &Path, name: &str) -> PathBuf {
+    root.join(name).join("config.json")
+}
+
+pub fn credential_path(root: &Path, name: &str) -> PathBuf {
+    root.join(name).join("credentials.json")
+}
+
+pub fn export_path(root: &Path, name: &str, extension: &str) -> PathBuf {
+    root.join(name).join(format!("orders.{}", extension))
+}
+
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("账户名称不能为空".to_string());
+    }
+    if name.starts_with('.') || name.chars().any(|c| "/\\:*?\"<>|".contains(c)) {
+        return Err("账户名称不能以.开头，也不能包含/\\:*?\"<>|".to_string());
+    }
+    Ok(())
+}
+
+pub fn list_profiles(root: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    names
+}
+
+pub fn create_profile(root: &Path, name: &str) -> Result<(), String> {
+    validate_profile_name(name)?;
+    let dir = root.join(name);
+    if dir.exists() {
+        return Err(format!("账户{}已存在", name));
+    }
+    fs::create_dir_all(dir).map_err(|e| format!("创建账户目录失败: {}", e))
+}
+
+pub fn delete_profile(root: &Path, name: &str) -> Result<(), String> {
+    validate_profile_name(name)?;
+    fs::remove_dir_all(root.join(name)).map_err(|e| format!("删除账户失败: {}", e))
+}
+
+//上次使用的账户，没有记录时使用默认账户
+pub fn current_profile(root: &Path) -> String {
+    fs::read(root.join("profiles.json"))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<ProfileIndex>(&bytes).ok())
+        .map(|index| index.current)
+        .filter(|name| validate_profile_name(name).is_ok())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+pub fn set_current_profile(root: &Path, name: &str) -> Result<(), String> {
+    fs::create_dir_all(root.join(name)).map_err(|e| format!("创建账户目录失败: {}", e))?;
+    let index = ProfileIndex {
+        current: name.to_string(),
+    };
+    let json = serde_json::to_string(&index).map_err(|e| e.to_string())?;
+    fs::write(root.join("profiles.json"), json).map_err(|e| format!("保存账户列表失败: {}", e))
+}
+
+//旧版本的config.json和credentials.json放在工作目录下，移动到默认账户目录
+pub fn migrate_legacy_files(root: &Path, legacy_dir: &Path) -> Result<bool, String> {
+    let target = root.join(DEFAULT_PROFILE);
+    let legacy: Vec<(PathBuf, PathBuf)> = [
+        ("config.json", config_path(root, DEFAULT_PROFILE)),
+        ("credentials.json", credential_path(root, DEFAULT_PROFILE)),
+    ]
+    .into_iter()
+    .map(|(file, to)| (legacy_dir.join(file), to))
+    .filter(|(from, to)| from.exists() && !to.exists())
+    .collect();
+    if legacy.is_empty() {
+        return Ok(false);
+    }
+    fs::create_dir_all(&target).map_err(|e| format!("创建账户目录失败: {}", e))?;
+    for (from, to) in legacy {
+        fs::rename(&from, &to).map_err(|e| format!("迁移{}失败: {}", from.display(), e))?;
+    }
+    Ok(true)
+}
@@ -0,0 +1,67 @@
+use eframe::egui::{Color32, ColorImage, Image, TextureHandle, TextureOptions, Ui, Vec2};
+use qrcode::{Color, QrCode};
+
+const MODULE_PIXELS: usize = 8; //每个码元的像素宽度
+const QUIET_ZONE: usize = 4; //四周留白的码元数
+
+//在本地生成二维码图片，登录链接和付款链接不会发给第三方
+pub fn qr_image(data: &str) -> Option<ColorImage> {
+    let code = QrCode::new(data.as_bytes()).ok()?;
+    let width = code.width();
+    let size = (width + QUIET_ZONE * 2) * MODULE_PIXELS;
+    let mut pixels = vec![Color32::WHITE; size * size];
+    for (i, color) in code.to_colors().into_iter().enumerate() {
+        if color != Color::Dark {
+            continue;
+        }
+        let x = (i % width + QUIET_ZONE) * MODULE_PIXELS;
+        let y = (i / width + QUIET_ZONE) * MODULE_PIXELS;
+        for row in y..y + MODULE_PIXELS {
+            pixels[row * size + x..row * size + x + MODULE_PIXELS].fill(Color32::BLACK);
+        }
+    }
+    Some(ColorImage {
+        size: [size, size],
+        pixels,
+    })
+}
+
+//按内容缓存二维码纹理，内容变化时重新生成，生成失败也缓存，不在每帧重试
+#[derive(Default)]
+pub struct QrTexture {
+    data: String,
+    texture: Option<Result<TextureHandle, ()>>,
+}
+
+impl QrTexture {
+    pub fn show(&mut self, ui: &mut Ui, data: &str, size: Vec2) {
+        if data.is_empty() {
+            return;
+        }
+        if self.data != data || self.texture.is_none() {
+            self.data = data.to_string();
+            self.texture = Some(
+                qr_image(data)
+                    .map(|image| {
+                        ui.ctx()
+                            .load_texture("qrcode", image, TextureOptions::NEAREST)
+                    })
+                    .ok_or(()),
+            );
+        }
+        match &self.texture {
+            Some(Ok(texture)) => {
+                ui.add(Image::new(texture).fit_to_exact_size(size));
+            }
+            Some(Err(())) => {
+                ui.label(format!("生成二维码失败，请直接打开链接: {}", data));
+            }
+            None => {}
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.texture = None;
+    }
+}
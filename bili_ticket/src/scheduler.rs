@@ -0,0 +1,120 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+#[derive(PartialEq, Debug)]
+pub enum ScheduleEvent {
+    Remind, //即将开售
+    Open,   //已到开售时间
+}
+
+//开售倒计时，时间均为服务器时间的毫秒时间戳
+pub struct SaleSchedule {
+    pub sku_id: i64,
+    pub sale_start: i64,
+    pub remind_before: Duration,
+    pub auto_buy: bool,
+    reminded: bool,
+    opened: bool,
+}
+
+impl SaleSchedule {
+    pub fn new(sku_id: i64, sale_start: i64, remind_before: Duration, auto_buy: bool) -> Self {
+        SaleSchedule {
+            sku_id,
+            sale_start,
+            remind_before,
+            auto_buy,
+            reminded: false,
+            opened: false,
+        }
+    }
+
+    pub fn remaining(&self, now: i64) -> i64 {
+        self.sale_start - now
+    }
+
+    pub fn is_opened(&self) -> bool {
+        self.opened
+    }
+
+    //每个事件只触发一次，开售时如果还没提醒过就不再提醒
+    pub fn poll(&mut self, now: i64) -> Option<ScheduleEvent> {
+        let remaining = self.remaining(now);
+        if !self.opened && remaining <= 0 {
+            self.opened = true;
+            self.reminded = true;
+            return Some(ScheduleEvent::Open);
+        }
+        if !self.reminded && remaining <= self.remind_before.as_millis() as i64 {
+            self.reminded = true;
+            return Some(ScheduleEvent::Remind);
+        }
+        None
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum PaymentEvent {
+    Warn(u64), //剩余支付时间不超过该阈值，单位为秒
+    Expired,
+}
+
+//待支付订单的支付截止提醒，每个订单的每个阈值只提醒一次
+#[derive(Default)]
+pub struct PaymentReminder {
+    warned: HashMap<String, u64>, //已经提醒过的最小阈值
+    expired: HashSet<String>,
+}
+
+impl PaymentReminder {
+    //剩余时间同时越过多个阈值时只按最小的阈值提醒一次，修改阈值后不会重复提醒更大的阈值
+    pub fn poll(
+        &mut self,
+        order_id: &str,
+        remaining: i64,
+        thresholds: &[u64],
+    ) -> Option<PaymentEvent> {
+        if remaining <= 0 {
+            return self
+                .expired
+                .insert(order_id.to_string())
+                .then_some(PaymentEvent::Expired);
+        }
+        let warned = self.warned.get(order_id).copied().unwrap_or(u64::MAX);
+        let threshold = thresholds
+            .iter()
+            .copied()
+            .filter(|threshold| remaining <= *threshold as i64 * 1000 && *threshold < warned)
+            .min()?;
+        self.warned.insert(order_id.to_string(), threshold);
+        Some(PaymentEvent::Warn(threshold))
+    }
+
+    //已支付或取消的订单不再跟踪
+    pub fn retain(&mut self, unpaid: &[String]) {
+        self.warned.retain(|order_id, _| unpaid.contains(order_id));
+        self.expired.retain(|order_id| unpaid.contains(order_id));
+    }
+}
+
+//剩余支付时间格式化为“mm:ss”
+pub fn format_pay_remain(remaining: i64) -> String {
+    let secs = remaining.max(0) / 1000;
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+//剩余毫秒数格式化为“x天 hh:mm:ss.s”
+pub fn format_countdown(remaining: i64) -> String {
+    let remaining = remaining.max(0);
+    let tenths = remaining % 1000 / 100;
+    let secs = remaining / 1000;
+    let (days, hours, minutes, secs) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if days > 0 {
+        format!(
+            "{}天 {:02}:{:02}:{:02}.{}",
+            days, hours, minutes, secs, tenths
+        )
+    } else {
+        format!("{:02}:{:02}:{:02}.{}", hours, minutes, secs, tenths)
+    }
+}
@@ -0,0 +1,87 @@
+use crate::app::NamePhoneForm;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub const SETTINGS_VERSION: u64 = 2;
+
+//第i项把版本i+1的设置升级到版本i+2
+const MIGRATIONS: [fn(Value) -> Value; 1] = [migrate_v1];
+
+//只保存需要跨次启动保留的偏好，登录凭据另存在凭据文件里，运行时状态不保存
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u64,
+    pub target_project: String,
+    pub screen_id: i64,
+    pub ticket_id: i64,
+    pub ticket_count: String,
+    pub name_phone_form: NamePhoneForm,
+    pub selected_buyers: Vec<i64>,
+    pub selected_address: i64,
+    pub remind_before_secs: u64, //开售前多少秒提醒
+    pub auto_buy_at_open: bool,  //开售时自动尝试购买一次
+    pub watch_projects: Vec<u64>,
+    pub watch_interval_secs: u64,
+    pub pay_warn_secs: Vec<u64>, //剩余支付时间提醒阈值
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            version: SETTINGS_VERSION,
+            target_project: String::default(),
+            screen_id: 0,
+            ticket_id: 0,
+            ticket_count: String::from("1"),
+            name_phone_form: NamePhoneForm::default(),
+            selected_buyers: vec![],
+            selected_address: 0,
+            remind_before_secs: 60,
+            auto_buy_at_open: false,
+            watch_projects: vec![],
+            watch_interval_secs: 30,
+            pay_warn_secs: vec![300, 60],
+        }
+    }
+}
+
+//没有version字段的是版本1，即旧版本把整个Config序列化得到的文件
+pub fn migrate_settings(mut json: Value) -> Result<Settings, String> {
+    let mut version = json.get("version").and_then(Value::as_u64).unwrap_or(1);
+    if version == 0 || version > SETTINGS_VERSION {
+        return Err(format!(
+            "不支持的设置文件版本{}，当前版本为{}",
+            version, SETTINGS_VERSION
+        ));
+    }
+    while version < SETTINGS_VERSION {
+        json = MIGRATIONS[version as usize - 1](json);
+        version += 1;
+    }
+    serde_json::from_value(json).map_err(|e| format!("设置文件格式错误: {}", e))
+}
+
+//版本1混有运行时状态，只保留偏好字段，选中的票档只保存id
+fn migrate_v1(json: Value) -> Value {
+    let mut settings = Map::new();
+    for key in [
+        "target_project",
+        "screen_id",
+        "ticket_count",
+        "name_phone_form",
+        "selected_buyers",
+        "selected_address",
+        "remind_before_secs",
+        "auto_buy_at_open",
+    ] {
+        if let Some(value) = json.get(key) {
+            settings.insert(key.to_string(), value.clone());
+        }
+    }
+    if let Some(ticket_id) = json.pointer("/ticket/id") {
+        settings.insert("ticket_id".to_string(), ticket_id.clone());
+    }
+    settings.insert("version".to_string(), 2.into());
+    Value::Object(settings)
+}
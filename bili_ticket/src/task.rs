@@ -1,221 +1,1184 @@
-use crate::app::{NamePhoneForm, BiliTicket, Config, OrderType};
+use crate::app::{AppState, BiliTicket, OrderType};
+use crate::log::{LogLevel, LogSource, Logger};
+use crate::profile::{
+    config_path, create_profile, credential_path, delete_profile, export_path, list_profiles,
+    PROFILE_ROOT,
+};
+use crate::scheduler::{
+    format_pay_remain, PaymentEvent, PaymentReminder, SaleSchedule, ScheduleEvent,
+};
+use crate::settings::Settings;
+use crate::watch::{diff_project, WATCH_MIN_INTERVAL};
 use bili_lib::{
-    cancel_order, generate_qrcode, nav_info, order_create, order_list_shows, order_prepare,
-    pay_param, project_info, qrcode_login, ClickPosition, CreateForm, PrepareForm,
+    load_credentials, save_credentials, Address, BiliClient, BiliResult, Buyer, BuyerForm,
+    ClickPosition, CreateForm, Credentials, DeliverInfo, DeliveryType, ExportFormat, Order,
+    OrderForm, PrepareForm, QrLogin, SessionHealth, Ticket, ORDER_PAGE_SIZE,
 };
-use reqwest::header::{HeaderMap, COOKIE};
-use serde_json::Error;
+use eframe::egui;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
-use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::time::sleep;
 
-pub fn load_config() -> Config {
-    Config::default()
+//按订单号更新已有订单，新订单加入列表，再按下单时间从新到旧排列
+pub fn merge_orders(orders: &mut Vec<Order>, page: Vec<Order>) {
+    for order in page {
+        match orders.iter_mut().find(|o| o.order_id == order.order_id) {
+            Some(old) => *old = order,
+            None => orders.push(order),
+        }
+    }
+    orders.sort_by_key(|order| std::cmp::Reverse(order.create_at));
+}
+
+//正在显示付款码的订单已不是待支付或者超过支付时间，刚下单还不在列表里的订单不算
+pub fn payment_stale(orders: &[Order], order_id: &str, now: i64) -> bool {
+    let Some(order) = orders.iter().find(|order| order.order_id == order_id) else {
+        return false;
+    };
+    !order.is_unpaid()
+        || order
+            .pay_deadline()
+            .is_some_and(|deadline| deadline * 1000 <= now)
+}
+
+//实名票每张票对应一个购票人，所选购票人数量必须和购买数量一致
+pub fn pick_buyers(buyers: &[Buyer], selected: &[i64], count: u8) -> Result<Vec<Buyer>, String> {
+    let picked: Vec<Buyer> = buyers
+        .iter()
+        .filter(|buyer| selected.contains(&buyer.id()))
+        .cloned()
+        .collect();
+    if picked.is_empty() {
+        return Err("请选择实名购票人".to_string());
+    }
+    if picked.len() != count as usize {
+        return Err(format!(
+            "购买数量({})与所选购票人数量({})不一致",
+            count,
+            picked.len()
+        ));
+    }
+    Ok(picked)
+}
+
+const CREATE_STATUS_INTERVAL: Duration = Duration::from_millis(500);
+const CREATE_STATUS_ATTEMPTS: u32 = 10;
+const CLOCK_SYNC_SAMPLES: u32 = 5;
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const SALE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+//下单并等待订单创建完成，成功时返回订单号；界面和开售倒计时的后台任务共用
+async fn submit_order(
+    client: &BiliClient,
+    logger: &Logger,
+    prepare_form: &PrepareForm,
+    order_form: OrderForm,
+    pay_money: u64,
+) -> Option<u64> {
+    let token = match client.order_prepare(prepare_form).await {
+        Ok(token) => token,
+        Err(e) => {
+            logger.log(
+                LogLevel::Error,
+                LogSource::Order,
+                format!("获取订单token失败，错误信息: {}", e).as_str(),
+            );
+            return None;
+        }
+    };
+    //let regex = Regex::new(r"deviceFingerprint=<device_id>;").unwrap();
+    //let cookie = self.state.cookie.lock().unwrap().to_string();
+    //let cap = regex.captures(cookie.as_str()).unwrap();
+    //let device_id = cap["device_id"].to_string();
+    let device_id = "".to_string();
+    let now = client.clock().now_millis() as u128;
+    let create_form = CreateForm {
+        project_id: prepare_form.project_id,
+        screen_id: prepare_form.screen_id,
+        sku_id: prepare_form.sku_id,
+        count: prepare_form.count,
+        pay_money,
+        order_type: 1,
+        timestamp: now,
+        token: token.clone(),
+        device_id,
+        click_position: ClickPosition {
+            x: 935,
+            y: 786,
+            origin: now - 1000,
+            now,
+        },
+        new_risk: false,
+        request_source: "pc_new".to_string(),
+        order_form,
+    };
+    let order_id = match client.order_create(&create_form).await {
+        Ok(order_id) => order_id,
+        Err(e) => {
+            logger.log(
+                LogLevel::Error,
+                LogSource::Order,
+                format!("购票失败，错误信息: {}", e).as_str(),
+            );
+            return None;
+        }
+    };
+    logger.log(
+        LogLevel::Info,
+        LogSource::Order,
+        "已提交订单，确认订单状态...",
+    );
+    match client
+        .wait_order_created(
+            prepare_form.project_id,
+            &token,
+            order_id,
+            CREATE_STATUS_INTERVAL,
+            CREATE_STATUS_ATTEMPTS,
+        )
+        .await
+    {
+        Ok(_) => {
+            logger.log(
+                LogLevel::Info,
+                LogSource::Order,
+                format!("购票成功，订单号: {}", order_id).as_str(),
+            );
+            Some(order_id)
+        }
+        Err(e) => {
+            logger.log(
+                LogLevel::Error,
+                LogSource::Order,
+                format!("订单创建失败，错误信息: {}", e).as_str(),
+            );
+            None
+        }
+    }
 }
 
 impl BiliTicket {
-    pub fn buy_ticket_now(&self, prepare_form: &PrepareForm) {
-        match self.config.order_type {
-            OrderType::Anonymous => {
+    pub fn prepare_form(&self) -> PrepareForm {
+        PrepareForm {
+            project_id: self
+                .settings
+                .target_project
+                .trim()
+                .parse()
+                .unwrap_or_default(),
+            screen_id: self.settings.screen_id,
+            order_type: 1,
+            count: self.settings.ticket_count.parse().unwrap_or(1),
+            sku_id: self.state.ticket.id,
+        }
+    }
 
+    //用服务器时间校准后开始开售倒计时，自动购票用的订单信息在这里确定
+    pub fn start_sale_schedule(&mut self, ctx: &egui::Context) {
+        let Some(sale_start) = self.state.ticket.sale_start_at() else {
+            self.log(
+                LogLevel::Error,
+                LogSource::Project,
+                "无法解析该票档的开售时间",
+            );
+            return;
+        };
+        let purchase = if self.settings.auto_buy_at_open {
+            if self.settings.ticket_count.parse::<u8>().unwrap_or(0) == 0 {
+                self.log(LogLevel::Warn, LogSource::Order, "购买数量不能为0");
+                return;
             }
-            OrderType::NamePhone => {
-                self.name_phone_buy_now(&prepare_form, &self.config.name_phone_form);
-            }
-            OrderType::Deliver => {}
-            OrderType::Buyer => {}
+            let prepare_form = self.prepare_form();
+            let Some(order_form) = self.order_form(&prepare_form) else {
+                return;
+            };
+            let pay_money = self.pay_money(&prepare_form, &order_form);
+            Some((prepare_form, order_form, pay_money))
+        } else {
+            None
+        };
+        if let Err(e) = self
+            .runtime
+            .block_on(self.client.sync_clock(CLOCK_SYNC_SAMPLES))
+        {
+            self.log(
+                LogLevel::Error,
+                LogSource::Project,
+                format!("校准服务器时间失败，错误信息: {}", e).as_str(),
+            );
+        }
+        *self.sale_schedule.lock().unwrap() = Some(SaleSchedule::new(
+            self.state.ticket.id,
+            sale_start * 1000,
+            Duration::from_secs(self.settings.remind_before_secs),
+            purchase.is_some(),
+        ));
+        let clock = self.client.clock();
+        let accuracy = match clock.uncertainty() {
+            Some(uncertainty) => format!(
+                "本地时间与服务器相差{}毫秒，误差±{}毫秒",
+                clock.offset(),
+                uncertainty
+            ),
+            None => "未能校准服务器时间，使用本地时间".to_string(),
+        };
+        self.log(
+            LogLevel::Info,
+            LogSource::Project,
+            format!("开始{}的开售倒计时，{}", self.state.ticket.desc, accuracy).as_str(),
+        );
+        self.handler_sale.store(false, Ordering::Relaxed);
+        self.handler_sale = Arc::new(AtomicBool::new(true));
+        self.handler_sale(purchase, ctx.clone());
+    }
 
+    pub fn stop_sale_schedule(&mut self) {
+        self.handler_sale.store(false, Ordering::Relaxed);
+        *self.sale_schedule.lock().unwrap() = None;
+    }
 
+    //倒计时在后台进行，窗口最小化时也能按时提醒和下单，界面只负责显示，
+    //有事件时主动请求重绘，提醒窗口和付款码不用等其它输入
+    fn handler_sale(&self, purchase: Option<(PrepareForm, OrderForm, u64)>, ctx: egui::Context) {
+        let cl = Arc::clone(&self.client);
+        let is_handler = Arc::clone(&self.handler_sale);
+        let schedule = Arc::clone(&self.sale_schedule);
+        let alerts = Arc::clone(&self.watch_alerts);
+        let sale_order = Arc::clone(&self.sale_order);
+        let logger = self.logger.clone();
+        let desc = self.state.ticket.desc.clone();
+        self.runtime.spawn(async move {
+            loop {
+                if !is_handler.load(Ordering::Relaxed) {
+                    return;
+                }
+                let now = cl.clock().now_millis();
+                let Some((event, remaining)) = schedule
+                    .lock()
+                    .unwrap()
+                    .as_mut()
+                    .map(|schedule| (schedule.poll(now), schedule.remaining(now)))
+                else {
+                    return;
+                };
+                match event {
+                    Some(ScheduleEvent::Remind) => {
+                        let message = format!("{}即将开售，请做好准备", desc);
+                        logger.log(LogLevel::Warn, LogSource::Project, &message);
+                        alerts.lock().unwrap().push(message);
+                        ctx.request_repaint();
+                    }
+                    Some(ScheduleEvent::Open) => {
+                        logger.log(
+                            LogLevel::Info,
+                            LogSource::Project,
+                            format!("{}已开售", desc).as_str(),
+                        );
+                        ctx.request_repaint();
+                        if let Some((prepare_form, order_form, pay_money)) = purchase {
+                            logger.log(LogLevel::Info, LogSource::Order, "开售自动购票...");
+                            if let Some(order_id) =
+                                submit_order(&cl, &logger, &prepare_form, order_form, pay_money)
+                                    .await
+                            {
+                                *sale_order.lock().unwrap() = Some(order_id);
+                                ctx.request_repaint();
+                            }
+                        }
+                        return;
+                    }
+                    None => ctx.request_repaint(),
+                }
+                //临近开售时缩短间隔，尽量准点下单
+                let wait = Duration::from_millis(remaining.max(1) as u64);
+                sleep(wait.min(SALE_POLL_INTERVAL)).await;
+            }
+        });
+    }
 
+    //开售时自动下单成功后，在界面上显示付款码
+    pub fn open_sale_order(&mut self) {
+        let order_id = self.sale_order.lock().unwrap().take();
+        if let Some(order_id) = order_id {
+            self.open_payment(order_id.to_string());
         }
     }
 
-    fn name_phone_buy_now(&self, prepare_form: &PrepareForm, anonymous_form: &NamePhoneForm) {
-        let token = self.prepare_order(prepare_form);
-        //let regex = Regex::new(r"deviceFingerprint=<device_id>;").unwrap();
-        //let cookie = self.config.cookie.lock().unwrap().to_string();
-        //let cap = regex.captures(cookie.as_str()).unwrap();
-        //let device_id = cap["device_id"].to_string();
-        let device_id = "".to_string();
-        let create_form = CreateForm {
-            project_id: prepare_form.project_id,
-            screen_id: prepare_form.screen_id,
-            sku_id: prepare_form.sku_id,
-            count: prepare_form.count,
-            pay_money: self.config.ticket.price * prepare_form.count as u64,
-            order_type: 1,
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis(),
-            token,
-            device_id,
-            click_position: ClickPosition {
-                x: 935,
-                y: 786,
-                origin: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis()
-                    - 1000,
-                now: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis(),
-            },
-            new_risk: false,
-            request_source: "pc_new".to_string(),
-            buyer: anonymous_form.name.clone(),
-            tel: anonymous_form.phone.clone(),
+    pub fn buy_ticket_now(&mut self, prepare_form: &PrepareForm) {
+        let Some(order_form) = self.order_form(prepare_form) else {
+            return;
         };
-        match self.runtime.block_on(order_create(
-            &self.client,
-            self.build_headers(),
-            &create_form,
-        )) {
-            Ok(order_id) => {
-                self.print_terminal("购票成功");
+        if let Some(order_id) = self.create_order(prepare_form, order_form) {
+            self.open_payment(order_id.to_string());
+        }
+    }
+
+    //按当前的购票方式填写订单信息，信息不全时提示并返回None
+    fn order_form(&self, prepare_form: &PrepareForm) -> Option<OrderForm> {
+        let name_phone_form = &self.settings.name_phone_form;
+        match self.state.order_type {
+            OrderType::Anonymous => Some(OrderForm::Anonymous),
+            OrderType::NamePhone => Some(OrderForm::NamePhone {
+                buyer: name_phone_form.name.clone(),
+                tel: name_phone_form.phone.clone(),
+            }),
+            OrderType::Deliver => {
+                let Some(address) = self
+                    .addresses
+                    .iter()
+                    .find(|address| address.id == self.settings.selected_address)
+                else {
+                    self.log(LogLevel::Warn, LogSource::Order, "请选择收货地址");
+                    return None;
+                };
+                Some(OrderForm::Deliver {
+                    buyer: name_phone_form.name.clone(),
+                    tel: name_phone_form.phone.clone(),
+                    deliver_info: DeliverInfo::from(address),
+                })
             }
-            Err(e) => self.print_terminal(format!("购票失败，错误信息: {}\n", e).as_str()),
+            OrderType::Buyer => match pick_buyers(
+                &self.buyers,
+                &self.settings.selected_buyers,
+                prepare_form.count,
+            ) {
+                Ok(buyers) => Some(OrderForm::Buyer(buyers)),
+                Err(e) => {
+                    self.log(LogLevel::Warn, LogSource::Order, &e);
+                    None
+                }
+            },
         }
     }
 
-    pub fn prepare_order(&self, prepare_form: &PrepareForm) -> String {
-        let token = self.runtime.block_on(order_prepare(
+    //当前场次的运费，免运费时为0
+    pub fn express_fee(&self) -> u64 {
+        self.state
+            .project
+            .as_ref()
+            .and_then(|project| {
+                project
+                    .screen_list
+                    .iter()
+                    .find(|screen| screen.id == self.settings.screen_id)
+            })
+            .map(|screen| screen.express_fee.max(0) as u64)
+            .unwrap_or(0)
+    }
+
+    //票价乘以数量，邮寄时加上运费，单位为分
+    fn pay_money(&self, prepare_form: &PrepareForm, order_form: &OrderForm) -> u64 {
+        let express_fee = match order_form {
+            OrderForm::Deliver { .. } => self.express_fee(),
+            _ => 0,
+        };
+        self.state.ticket.price * prepare_form.count as u64 + express_fee
+    }
+
+    fn create_order(&self, prepare_form: &PrepareForm, order_form: OrderForm) -> Option<u64> {
+        let pay_money = self.pay_money(prepare_form, &order_form);
+        self.runtime.block_on(submit_order(
             &self.client,
-            self.build_headers(),
+            &self.logger,
             prepare_form,
-        ));
-        token.unwrap()
+            order_form,
+            pay_money,
+        ))
     }
 
-    pub fn cancel_order(&self, order_id: &String) {
-        match self
-            .runtime
-            .block_on(cancel_order(&self.client, self.build_headers(), order_id))
+    //显示订单的付款二维码和剩余支付时间
+    pub fn open_payment(&mut self, order_id: String) {
+        self.log(LogLevel::Info, LogSource::Payment, "请求付款二维码...");
+        if !self.do_paying(order_id.clone()) {
+            return;
+        }
+        self.state.show_paying_qr = true;
+        self.state.select_order_id = order_id.clone();
+        self.pay_deadline = None;
+        match self.runtime.block_on(self.client.order_info(&order_id)) {
+            Ok(order) => {
+                self.pay_deadline = order.pay_deadline().map(|deadline| deadline * 1000);
+            }
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    LogSource::Order,
+                    format!("获取订单详情失败，错误信息: {}", e).as_str(),
+                );
+            }
+        }
+    }
+
+    pub fn clear_payment(&mut self) {
+        self.pay_qr.clear();
+        self.state.show_paying_qr = false;
+        self.state.pay_code = String::default();
+        self.state.select_order_id = String::default();
+        self.pay_deadline = None;
+    }
+
+    //到达提醒阈值时提示并返回true，订单超过支付时间后清除付款码
+    pub fn check_payment_deadlines(&mut self, orders: &[Order]) -> bool {
+        let now = self.client.clock().now_millis();
+        let unpaid: Vec<String> = orders
+            .iter()
+            .filter(|order| order.is_unpaid())
+            .map(|order| order.order_id.clone())
+            .collect();
+        self.pay_reminder.retain(&unpaid);
+        let mut attention = false;
+        for order in orders {
+            let Some(deadline) = order.pay_deadline() else {
+                continue;
+            };
+            let remaining = deadline * 1000 - now;
+            match self
+                .pay_reminder
+                .poll(&order.order_id, remaining, &self.settings.pay_warn_secs)
+            {
+                Some(PaymentEvent::Warn(_)) => {
+                    attention = true;
+                    self.log(
+                        LogLevel::Warn,
+                        LogSource::Payment,
+                        format!(
+                            "订单{}剩余支付时间{}，请尽快支付",
+                            order.order_id,
+                            format_pay_remain(remaining)
+                        )
+                        .as_str(),
+                    );
+                }
+                Some(PaymentEvent::Expired) => {
+                    self.log(
+                        LogLevel::Warn,
+                        LogSource::Payment,
+                        format!("订单{}已超过支付时间", order.order_id).as_str(),
+                    );
+                    if self.state.select_order_id == order.order_id {
+                        self.clear_payment();
+                    }
+                }
+                None => {}
+            }
+        }
+        if !self.state.select_order_id.is_empty()
+            && payment_stale(orders, &self.state.select_order_id, now)
         {
+            self.log(
+                LogLevel::Info,
+                LogSource::Payment,
+                format!("订单{}已不需要支付，清除付款码", self.state.select_order_id).as_str(),
+            );
+            self.clear_payment();
+        }
+        //刚下单的订单可能还不在列表里，按打开付款码时查到的截止时间清除
+        if self.pay_deadline.is_some_and(|deadline| deadline <= now) {
+            self.log(LogLevel::Warn, LogSource::Payment, "付款码已过期");
+            self.clear_payment();
+        }
+        attention
+    }
+
+    pub fn load_order_detail(&mut self, order_id: &str) {
+        match self.runtime.block_on(self.client.order_info(order_id)) {
+            Ok(order) => self.order_detail = Some(order),
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    LogSource::Order,
+                    format!("获取订单详情失败，错误信息: {}", e).as_str(),
+                );
+            }
+        }
+    }
+
+    pub fn cancel_order(&self, order_id: &str) {
+        match self.runtime.block_on(self.client.cancel_order(order_id)) {
             Ok(_) => {
-                self.print_terminal("取消订单成功!\n");
+                self.log(LogLevel::Info, LogSource::Order, "取消订单成功!");
             }
-            Err(_) => {
-                self.print_terminal("取消订单失败，可能是订单不存在?\n");
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    LogSource::Order,
+                    format!("取消订单失败，错误信息: {}", e).as_str(),
+                );
             }
         };
     }
 
-    pub fn print_terminal(&self, str: &str) {
-        let tb = Arc::clone(&self.terminal_buffer);
-        if !tb.lock().unwrap().ends_with("\n") {
-            tb.lock().unwrap().push('\n');
+    pub fn config_path(&self) -> PathBuf {
+        config_path(Path::new(PROFILE_ROOT), &self.profile)
+    }
+    pub fn credential_path(&self) -> PathBuf {
+        credential_path(Path::new(PROFILE_ROOT), &self.profile)
+    }
+    pub fn save_profile(&self) {
+        self.save_settings();
+        //未解锁时保留原凭据文件，避免被空cookie覆盖
+        if !self.credential_locked {
+            self.store_credentials();
+        }
+    }
+    pub fn switch_profile(&mut self, name: &str) {
+        if name == self.profile {
+            return;
+        }
+        self.save_profile();
+        self.reset_session_state();
+        self.profile = name.to_string();
+        self.load_profile();
+        self.log(
+            LogLevel::Info,
+            LogSource::System,
+            format!("已切换到账户{}", name).as_str(),
+        );
+    }
+    pub fn new_profile(&mut self) {
+        let name = self.profile_name_input.trim().to_string();
+        if let Err(e) = create_profile(Path::new(PROFILE_ROOT), &name) {
+            self.log(
+                LogLevel::Error,
+                LogSource::System,
+                format!("新建账户失败，错误信息: {}", e).as_str(),
+            );
+            return;
+        }
+        self.profile_name_input.clear();
+        self.switch_profile(&name);
+    }
+    pub fn remove_profile(&mut self, name: &str) {
+        if name == self.profile {
+            self.log(LogLevel::Warn, LogSource::System, "不能删除正在使用的账户");
+            return;
+        }
+        match delete_profile(Path::new(PROFILE_ROOT), name) {
+            Ok(()) => self.log(
+                LogLevel::Info,
+                LogSource::System,
+                format!("已删除账户{}", name).as_str(),
+            ),
+            Err(e) => self.log(
+                LogLevel::Error,
+                LogSource::System,
+                format!("删除账户失败，错误信息: {}", e).as_str(),
+            ),
         }
-        tb.lock().unwrap().push_str(str);
+        self.profiles = list_profiles(Path::new(PROFILE_ROOT));
+    }
+    //后台任务持有旧的标志和客户端，全部换成新的，旧任务自行退出且不会影响新账户
+    fn reset_session_state(&mut self) {
+        for flag in [
+            &self.logging,
+            &self.handler_order,
+            &self.handler_session,
+            &self.handler_watch,
+            &self.handler_sale,
+        ] {
+            flag.store(false, Ordering::Relaxed);
+        }
+        self.logging = Arc::new(AtomicBool::new(false));
+        self.handler_order = Arc::new(AtomicBool::new(false));
+        self.handler_session = Arc::new(AtomicBool::new(false));
+        self.credentials_refreshed = Arc::new(AtomicBool::new(false));
+        self.handler_watch = Arc::new(AtomicBool::new(false));
+        self.handler_sale = Arc::new(AtomicBool::new(false));
+        self.watch_snapshots = Arc::new(Mutex::new(HashMap::new()));
+        self.watch_alerts = Arc::new(Mutex::new(vec![]));
+        self.watch_alerts_seen = 0;
+        self.show_watch_window = false;
+        self.watch_input.clear();
+        self.order_detail = None;
+        self.orders_total = Arc::new(AtomicUsize::new(0));
+        self.client = Arc::new(BiliClient::default());
+        self.settings = Settings::default();
+        self.state = AppState::default();
+        self.show_login_qr = false;
+        self.login_qr_url.clear();
+        self.login_qr.clear();
+        self.pay_qr.clear();
+        self.pay_deadline = None;
+        self.pay_reminder = PaymentReminder::default();
+        self.order_next_page = 1;
+        self.first_page_full = Arc::new(AtomicBool::new(false));
+        self.last_page_full = false;
+        self.show_buyer_window = false;
+        self.buyers.clear();
+        self.buyer_form = BuyerForm::default();
+        self.addresses.clear();
+        self.address_form = Address::default();
+        self.credential_locked = false;
+        self.credential_unreadable = false;
+        self.show_credential_window = false;
+        self.passphrase_input.clear();
+        self.passphrase = None;
+        self.sale_schedule = Arc::new(Mutex::new(None));
+        self.sale_order = Arc::new(Mutex::new(None));
+    }
+    pub fn save_settings(&self) {
+        match serde_json::to_string(&self.settings) {
+            Ok(json) => {
+                if let Err(e) = fs::write(self.config_path(), json) {
+                    self.log(
+                        LogLevel::Error,
+                        LogSource::System,
+                        format!("保存配置失败，错误信息: {}", e).as_str(),
+                    );
+                }
+            }
+            Err(e) => self.log(
+                LogLevel::Error,
+                LogSource::System,
+                format!("保存配置失败，错误信息: {}", e).as_str(),
+            ),
+        }
+    }
+    pub fn set_session_cookie(&self, cookie: &str) {
+        self.client.set_cookie(cookie);
+        *self.state.cookie.lock().unwrap() = cookie.to_string();
+    }
+    pub fn restore_session(&self, credentials: &Credentials) {
+        self.set_session_cookie(&credentials.cookie);
+        self.state
+            .is_login
+            .store(!credentials.cookie.is_empty(), Ordering::Relaxed);
+        *self.state.refresh_token.lock().unwrap() = credentials.refresh_token.clone();
+    }
+    pub fn store_credentials(&self) {
+        let credentials = Credentials {
+            cookie: self.state.cookie.lock().unwrap().clone(),
+            refresh_token: self.state.refresh_token.lock().unwrap().clone(),
+        };
+        //解密失败的凭据文件不能被空凭据覆盖
+        if self.credential_unreadable && credentials.cookie.is_empty() {
+            return;
+        }
+        if let Err(e) = save_credentials(
+            &self.credential_path(),
+            &credentials,
+            self.passphrase.as_deref(),
+        ) {
+            self.log(
+                LogLevel::Error,
+                LogSource::Login,
+                format!("保存登录凭据失败，错误信息: {}", e).as_str(),
+            );
+        }
+    }
+    pub fn unlock_credentials(&mut self) -> Result<(), String> {
+        let passphrase = self.passphrase_input.clone();
+        let credentials = load_credentials(&self.credential_path(), Some(&passphrase))
+            .map_err(|e| e.to_string())?
+            .ok_or("凭据文件不存在")?;
+        self.restore_session(&credentials);
+        self.state.is_login.store(true, Ordering::Relaxed);
+        self.passphrase = Some(passphrase);
+        self.passphrase_input.clear();
+        self.credential_locked = false;
+        self.show_credential_window = false;
+        Ok(())
+    }
+    pub fn log(&self, level: LogLevel, source: LogSource, message: &str) {
+        self.logger.log(level, source, message);
     }
 
     pub fn do_paying(&mut self, order_id: String) -> bool {
-        match self
-            .runtime
-            .block_on(pay_param(&self.client, self.build_headers(), &order_id))
-        {
+        match self.runtime.block_on(self.client.pay_param(&order_id)) {
             Ok(url) => {
-                self.config.pay_code = format!(
-                    "https://api.pwmqr.com/qrcode/create/?url={}",
-                    url.replace("&", "%26")
-                );
+                self.state.pay_code = url;
                 true
             }
-            Err(_) => {
-                self.print_terminal("请求支付码失败，可能是订单不存在?\n");
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    LogSource::Payment,
+                    format!("请求支付码失败，错误信息: {}", e).as_str(),
+                );
                 false
             }
         }
     }
-    pub fn do_login(&mut self) {
-        let (url, qrcode_key) = self.runtime.block_on(generate_qrcode(&self.client));
-        // let qrcode = QRBuilder::new(url).build().unwrap();
-        // self.login_qr = ImageBuilder::default()
-        //     .shape(Shape::RoundedSquare)
-        //     .background_color([255, 255, 255, 0])
-        //     .fit_width(250)
-        //     .to_bytes(&qrcode)
-        //     .unwrap();
-        self.login_qr_url = format!(
-            "https://api.pwmqr.com/qrcode/create/?url={}",
-            url.replace("&", "%26")
-        );
-        self.print_terminal("请扫描二维码登录:\n");
+    pub fn do_login(&mut self) -> BiliResult<()> {
+        let qrcode = self.runtime.block_on(self.client.generate_qrcode())?;
+        self.login_qr_url = qrcode.url.clone();
+        self.log(LogLevel::Info, LogSource::Login, "请扫描二维码登录:");
         self.show_login_qr = true;
         let logging = Arc::clone(&self.logging);
-        let tb = Arc::clone(&self.terminal_buffer);
-        let c = Arc::clone(&self.config.cookie);
+        let logger = self.logger.clone();
+        let c = Arc::clone(&self.state.cookie);
+        let rt = Arc::clone(&self.state.refresh_token);
         let cl = Arc::clone(&self.client);
-        let is_l = Arc::clone(&self.config.is_login);
+        let is_l = Arc::clone(&self.state.is_login);
         self.runtime.spawn(async move {
             loop {
                 sleep(Duration::from_secs(3)).await;
-                let (code, msg, cookie) = qrcode_login(&cl, &qrcode_key).await;
-                match code {
-                    0 => {
-                        *c.lock().unwrap() = cookie.unwrap();
+                //切换账户后放弃旧的扫码登录
+                if !logging.load(Ordering::Relaxed) {
+                    return;
+                }
+                match cl.qrcode_login(&qrcode.qrcode_key).await {
+                    Ok(QrLogin {
+                        code: 0,
+                        cookie: Some(cookie),
+                        refresh_token,
+                        ..
+                    }) => {
+                        *c.lock().unwrap() = cookie.to_string();
+                        *rt.lock().unwrap() = refresh_token;
+                        cl.set_cookie_jar(cookie);
                         is_l.store(true, Ordering::Relaxed);
-                        tb.lock().unwrap().push_str("登录成功!\n");
+                        logger.log(LogLevel::Info, LogSource::Login, "登录成功!");
                         logging.store(false, Ordering::Relaxed);
                         break;
                     }
-                    _ => {
+                    Ok(_) => {
+                        continue;
+                    }
+                    Err(e) => {
+                        logger.log(
+                            LogLevel::Error,
+                            LogSource::Login,
+                            format!("查询登录状态失败，错误信息: {}", e).as_str(),
+                        );
                         continue;
                     }
                 }
             }
         });
+        Ok(())
+    }
+
+    //启动时和之后每隔一段时间检查登录状态，需要时刷新cookie，失效时退回扫码登录
+    pub fn handler_session(&self) {
+        let cl = Arc::clone(&self.client);
+        let is_handler = Arc::clone(&self.handler_session);
+        let is_handler_order = Arc::clone(&self.handler_order);
+        let is_l = Arc::clone(&self.state.is_login);
+        let c = Arc::clone(&self.state.cookie);
+        let rt = Arc::clone(&self.state.refresh_token);
+        let refreshed = Arc::clone(&self.credentials_refreshed);
+        let logger = self.logger.clone();
+        self.runtime.spawn(async move {
+            let mut warned_no_refresh = false;
+            loop {
+                if !is_handler.load(Ordering::Relaxed) {
+                    return;
+                }
+                match cl.session_health().await {
+                    Ok(SessionHealth::Valid) => {}
+                    Ok(SessionHealth::ValidNoRefresh) => {
+                        if !warned_no_refresh {
+                            warned_no_refresh = true;
+                            logger.log(LogLevel::Warn, LogSource::Login, "登录有效，但当前凭据缺少bili_jct，无法自动刷新，请重新扫码登录以启用自动刷新");
+                        }
+                    }
+                    Ok(SessionHealth::NeedsRefresh) => {
+                        let refresh_token = rt.lock().unwrap().clone();
+                        if refresh_token.is_empty() {
+                            logger.log(LogLevel::Warn, LogSource::Login, "登录凭据即将过期，且没有可用的refresh_token，过期后需要重新扫码登录");
+                        } else {
+                            match cl.refresh_cookie(&refresh_token).await {
+                                Ok(refresh_token) => {
+                                    *rt.lock().unwrap() = refresh_token;
+                                    *c.lock().unwrap() = cl.cookie();
+                                    refreshed.store(true, Ordering::Relaxed);
+                                    logger.log(LogLevel::Info, LogSource::Login, "登录凭据已刷新");
+                                }
+                                Err(e) => logger.log(LogLevel::Error, LogSource::Login,
+                                    format!("刷新登录凭据失败，错误信息: {}", e).as_str(),
+                                ),
+                            }
+                        }
+                    }
+                    Ok(SessionHealth::Expired) => {
+                        cl.set_cookie("");
+                        c.lock().unwrap().clear();
+                        rt.lock().unwrap().clear();
+                        is_handler_order.store(false, Ordering::Relaxed);
+                        is_handler.store(false, Ordering::Relaxed);
+                        is_l.store(false, Ordering::Relaxed);
+                        logger.log(LogLevel::Warn, LogSource::Login, "登录已失效，请重新扫码登录");
+                        return;
+                    }
+                    Err(e) => logger.log(LogLevel::Error, LogSource::Login, format!("检查登录状态失败，错误信息: {}", e).as_str()),
+                }
+                sleep(SESSION_CHECK_INTERVAL).await;
+            }
+        });
+    }
+
+    //定时查询关注的票品，状态变化输出到终端，有票档变为可购买时弹窗提醒
+    pub fn handler_watch(&self) {
+        let cl = Arc::clone(&self.client);
+        let is_handler = Arc::clone(&self.handler_watch);
+        let snapshots = Arc::clone(&self.watch_snapshots);
+        let alerts = Arc::clone(&self.watch_alerts);
+        let logger = self.logger.clone();
+        let project_ids = self.settings.watch_projects.clone();
+        let interval =
+            Duration::from_secs(self.settings.watch_interval_secs).max(WATCH_MIN_INTERVAL);
+        self.runtime.spawn(async move {
+            loop {
+                for &project_id in &project_ids {
+                    if !is_handler.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    match cl.project_info(project_id).await {
+                        Ok(project) => {
+                            //第一次查询只记录状态，之后和上一次比较
+                            let Some(old) = snapshots
+                                .lock()
+                                .unwrap()
+                                .insert(project_id, project.clone())
+                            else {
+                                continue;
+                            };
+                            for change in diff_project(&old, &project) {
+                                logger.log(
+                                    LogLevel::Info,
+                                    LogSource::Project,
+                                    format!("关注的票品有变化: {}", change).as_str(),
+                                );
+                                if change.became_purchasable() {
+                                    alerts
+                                        .lock()
+                                        .unwrap()
+                                        .push(format!("{}，可以购买了", change));
+                                }
+                            }
+                        }
+                        Err(e) => logger.log(
+                            LogLevel::Error,
+                            LogSource::Project,
+                            format!("查询关注的票品{}失败，错误信息: {}", project_id, e).as_str(),
+                        ),
+                    }
+                }
+                if !is_handler.load(Ordering::Relaxed) {
+                    return;
+                }
+                sleep(interval).await;
+            }
+        });
+    }
+    //关注列表或间隔变化后换一个新标志重启，旧任务自行退出，已记录的状态保留
+    pub fn start_watch(&mut self) {
+        self.handler_watch.store(false, Ordering::Relaxed);
+        self.handler_watch = Arc::new(AtomicBool::new(true));
+        self.handler_watch();
+    }
+    pub fn add_watch_project(&mut self) {
+        let project_id = match self.watch_input.trim().parse::<u64>() {
+            Ok(project_id) => project_id,
+            Err(_) => {
+                self.log(LogLevel::Warn, LogSource::Project, "票品id格式错误");
+                return;
+            }
+        };
+        if self.settings.watch_projects.contains(&project_id) {
+            self.log(LogLevel::Warn, LogSource::Project, "该票品已在关注列表中");
+            return;
+        }
+        self.settings.watch_projects.push(project_id);
+        self.watch_input.clear();
+        if self.handler_watch.load(Ordering::Relaxed) {
+            self.start_watch();
+        }
+    }
+    pub fn remove_watch_project(&mut self, project_id: u64) {
+        self.settings.watch_projects.retain(|id| *id != project_id);
+        self.watch_snapshots.lock().unwrap().remove(&project_id);
+        if self.handler_watch.load(Ordering::Relaxed) {
+            self.start_watch();
+        }
     }
 
     pub fn handler_orders(&self) {
         let cl = Arc::clone(&self.client);
-        let orders = Arc::clone(&self.config.orders);
-        let headers = self.build_headers();
+        let orders = Arc::clone(&self.state.orders);
         let is_handler = Arc::clone(&self.handler_order);
+        let total = Arc::clone(&self.orders_total);
+        let first_page_full = Arc::clone(&self.first_page_full);
+        let logger = self.logger.clone();
         self.runtime.spawn(async move {
             loop {
                 if !is_handler.load(Ordering::Relaxed) {
                     return;
                 }
-                let res = order_list_shows(&cl, headers.clone()).await;
-                *orders.lock().unwrap() = res;
+                match cl.order_list_shows(0, ORDER_PAGE_SIZE).await {
+                    Ok(res) => {
+                        first_page_full.store(
+                            res.list.len() >= ORDER_PAGE_SIZE as usize,
+                            Ordering::Relaxed,
+                        );
+                        let mut orders = orders.lock().unwrap();
+                        merge_orders(&mut orders, res.list);
+                        //total可能缺失，至少是已加载的数量
+                        total.store(res.total.max(orders.len()), Ordering::Relaxed);
+                    }
+                    Err(e) => logger.log(
+                        LogLevel::Error,
+                        LogSource::Order,
+                        format!("加载订单数据失败，错误信息: {}", e).as_str(),
+                    ),
+                }
                 sleep(Duration::from_millis(1500)).await;
             }
         });
     }
 
-    pub fn get_user_head(&mut self) {
-        let (uname, face_img) = self
+    //和order_list_all一样按是否满页判断，total可能缺失
+    pub fn has_more_orders(&self) -> bool {
+        if self.order_next_page == 1 {
+            self.first_page_full.load(Ordering::Relaxed)
+        } else {
+            self.last_page_full
+        }
+    }
+
+    pub fn load_more_orders(&mut self) {
+        self.log(LogLevel::Info, LogSource::Order, "加载更早的订单...");
+        match self.runtime.block_on(
+            self.client
+                .order_list_shows(self.order_next_page, ORDER_PAGE_SIZE),
+        ) {
+            Ok(res) => {
+                self.last_page_full = res.list.len() >= ORDER_PAGE_SIZE as usize;
+                if !res.list.is_empty() {
+                    self.order_next_page += 1;
+                }
+                let mut orders = self.state.orders.lock().unwrap();
+                merge_orders(&mut orders, res.list);
+                self.orders_total
+                    .store(res.total.max(orders.len()), Ordering::Relaxed);
+            }
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    LogSource::Order,
+                    format!("加载订单数据失败，错误信息: {}", e).as_str(),
+                );
+            }
+        }
+    }
+
+    //导出全部订单到当前账户目录，报销时用表格打开
+    pub fn export_orders(&self, format: ExportFormat) {
+        self.log(LogLevel::Info, LogSource::Order, "导出全部订单...");
+        let path = export_path(Path::new(PROFILE_ROOT), &self.profile, format.extension());
+        let result = self
             .runtime
-            .block_on(nav_info(&self.client, self.build_headers()));
-        self.config.user_name = uname;
-        self.config.user_head_img_url = face_img;
+            .block_on(self.client.export_order_history(format))
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(&path, content).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => self.log(
+                LogLevel::Info,
+                LogSource::Order,
+                format!("订单已导出到{}", path.display()).as_str(),
+            ),
+            Err(e) => self.log(
+                LogLevel::Error,
+                LogSource::Order,
+                format!("导出订单失败，错误信息: {}", e).as_str(),
+            ),
+        }
     }
 
-    pub fn get_project(&mut self) -> Result<(), Error> {
-        let project = self.runtime.block_on(project_info(
-            &self.client,
-            self.config.target_project.parse().unwrap(),
-        ))?;
-        self.config.project = Option::from(project.clone());
-        if project.screen_list[0].ticket_list[0].anonymous_buy {
-            self.config.order_type = OrderType::Anonymous
-        } else if project.screen_list[0].delivery_type == 3 {
-            self.config.order_type = OrderType::Deliver;
+    pub fn load_addresses(&mut self) {
+        match self.runtime.block_on(self.client.address_list()) {
+            Ok(addresses) => {
+                if !addresses
+                    .iter()
+                    .any(|address| address.id == self.settings.selected_address)
+                {
+                    self.settings.selected_address = addresses
+                        .iter()
+                        .find(|address| address.def == 1)
+                        .map(|address| address.id)
+                        .unwrap_or_default();
+                }
+                self.addresses = addresses;
+            }
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    LogSource::Order,
+                    format!("加载收货地址失败，错误信息: {}", e).as_str(),
+                );
+            }
+        }
+    }
+
+    pub fn save_address(&mut self) {
+        let res = if self.address_form.id == 0 {
+            self.runtime
+                .block_on(self.client.address_add(&self.address_form))
+        } else {
+            self.runtime
+                .block_on(self.client.address_edit(&self.address_form))
+        };
+        match res {
+            Ok(_) => {
+                self.log(LogLevel::Info, LogSource::Order, "保存收货地址成功!");
+                self.address_form = Address::default();
+                self.load_addresses();
+            }
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    LogSource::Order,
+                    format!("保存收货地址失败，错误信息: {}", e).as_str(),
+                );
+            }
+        }
+    }
+
+    pub fn load_buyers(&mut self) {
+        match self.runtime.block_on(self.client.buyer_info()) {
+            Ok(buyers) => self.buyers = buyers,
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    LogSource::Order,
+                    format!("加载购票人失败，错误信息: {}", e).as_str(),
+                );
+            }
+        }
+    }
+
+    pub fn save_buyer(&mut self) {
+        let res = if self.buyer_form.id == 0 {
+            self.runtime
+                .block_on(self.client.buyer_create(&self.buyer_form))
+        } else {
+            self.runtime
+                .block_on(self.client.buyer_edit(&self.buyer_form))
+        };
+        match res {
+            Ok(_) => {
+                self.log(LogLevel::Info, LogSource::Order, "保存购票人成功!");
+                self.buyer_form = BuyerForm::default();
+                self.load_buyers();
+            }
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    LogSource::Order,
+                    format!("保存购票人失败，错误信息: {}", e).as_str(),
+                );
+            }
+        }
+    }
+
+    pub fn delete_buyer(&mut self, buyer_id: i64) {
+        match self.runtime.block_on(self.client.buyer_delete(buyer_id)) {
+            Ok(_) => {
+                self.log(LogLevel::Info, LogSource::Order, "删除购票人成功!");
+                self.load_buyers();
+            }
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    LogSource::Order,
+                    format!("删除购票人失败，错误信息: {}", e).as_str(),
+                );
+            }
+        }
+    }
+
+    pub fn set_default_buyer(&mut self, buyer: &Buyer) {
+        match self.runtime.block_on(self.client.buyer_set_default(buyer)) {
+            Ok(_) => {
+                self.log(LogLevel::Info, LogSource::Order, "设置默认购票人成功!");
+                self.load_buyers();
+            }
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    LogSource::Order,
+                    format!("设置默认购票人失败，错误信息: {}", e).as_str(),
+                );
+            }
+        }
+    }
+
+    pub fn get_user_head(&mut self) -> BiliResult<()> {
+        let nav = self.runtime.block_on(self.client.nav_info())?;
+        self.state.user_name = nav.uname;
+        self.state.user_head_img_url = nav.face;
+        Ok(())
+    }
+
+    pub fn get_project(&mut self) -> Result<(), String> {
+        let project_id = self
+            .settings
+            .target_project
+            .trim()
+            .parse()
+            .map_err(|_| "票品id格式错误".to_string())?;
+        let project = self
+            .runtime
+            .block_on(self.client.project_info(project_id))
+            .map_err(|e| e.to_string())?;
+        let screen = project
+            .screen_list
+            .first()
+            .ok_or_else(|| "票品没有可选场次".to_string())?;
+        let anonymous_buy = screen
+            .ticket_list
+            .first()
+            .map(|ticket| ticket.anonymous_buy)
+            .unwrap_or(false);
+        let delivery_type = screen.delivery_type;
+        self.state.project = Option::from(project.clone());
+        if anonymous_buy {
+            self.state.order_type = OrderType::Anonymous
+        } else if delivery_type == DeliveryType::Paper {
+            self.state.order_type = OrderType::Deliver;
+            self.load_addresses();
         } else if project.buyer_info == "2,1" {
-            self.config.order_type = OrderType::Buyer;
+            self.state.order_type = OrderType::Buyer;
+            self.load_buyers();
         } else if project.need_contact == 1 {
-            self.config.order_type = OrderType::NamePhone;
+            self.state.order_type = OrderType::NamePhone;
+        } else {
+            //不需要联系人的免费入场票品和匿名票品一样提交
+            self.state.order_type = OrderType::Anonymous;
         }
         Ok(())
     }
 
-    fn build_headers(&self) -> HeaderMap {
-        let mut headers = HeaderMap::new();
-        headers.insert(COOKIE, self.config.cookie.lock().unwrap().parse().unwrap());
-        headers
+    //按保存的场次和票档恢复上次的选择，票档已下架时需要重新选择
+    pub fn restore_ticket_selection(&mut self) {
+        let ticket = self.state.project.as_ref().and_then(|project| {
+            project
+                .screen_list
+                .iter()
+                .find(|screen| screen.id == self.settings.screen_id)
+                .and_then(|screen| {
+                    screen
+                        .ticket_list
+                        .iter()
+                        .find(|ticket| ticket.id == self.settings.ticket_id)
+                })
+                .cloned()
+        });
+        match ticket {
+            Some(ticket) => {
+                self.state.ticket = ticket;
+                self.state.is_select_ticket = true;
+            }
+            None => {
+                self.state.ticket = Ticket::default();
+                self.state.is_select_ticket = false;
+            }
+        }
     }
 }
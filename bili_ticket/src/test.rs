@@ -0,0 +1,315 @@
+use crate::credential::take_plaintext_cookie;
+use crate::log::{LogEvent, LogFile, LogLevel, LogSource, Logger};
+use crate::profile::{
+    config_path, create_profile, credential_path, current_profile, delete_profile, list_profiles,
+    migrate_legacy_files, set_current_profile, validate_profile_name, DEFAULT_PROFILE,
+};
+use crate::qr::qr_image;
+use crate::scheduler::{
+    format_countdown, format_pay_remain, PaymentEvent, PaymentReminder, SaleSchedule, ScheduleEvent,
+};
+use crate::settings::{migrate_settings, Settings, SETTINGS_VERSION};
+use crate::task::{merge_orders, payment_stale, pick_buyers};
+use crate::watch::{diff_project, WatchChange};
+use bili_lib::fixture::{buyer_json, order_json, project_json, screen_json, ticket_json};
+use bili_lib::{Buyer, Order, Project, SaleStatus};
+use chrono::TimeZone;
+use eframe::egui::Color32;
+use std::time::Duration;
+
+#[test]
+fn settings_round_trip_through_json() {
+    let settings = Settings {
+        target_project: "77789".to_string(),
+        ticket_id: 3,
+        ..Settings::default()
+    };
+    let json = serde_json::to_string(&settings).unwrap();
+    assert!(!json.contains("cookie"));
+    let loaded = migrate_settings(serde_json::from_str(&json).unwrap()).unwrap();
+    assert_eq!(loaded.version, SETTINGS_VERSION);
+    assert_eq!(loaded.target_project, "77789");
+    assert_eq!(loaded.ticket_id, 3);
+}
+
+#[test]
+fn legacy_config_migrates_to_settings() {
+    let mut json = serde_json::json!({
+        "cookie": "SESSDATA=secret",
+        "target_project": "77789",
+        "screen_id": 2,
+        "ticket": { "id": 5, "desc": "VIP" },
+        "is_login": true,
+        "show_paying_qr": true,
+        "ticket_count": "2",
+        "remind_before_secs": 30
+    });
+    assert_eq!(
+        take_plaintext_cookie(&mut json).as_deref(),
+        Some("SESSDATA=secret")
+    );
+    assert!(json.get("cookie").is_none());
+    let settings = migrate_settings(json).unwrap();
+    assert_eq!(settings.version, SETTINGS_VERSION);
+    assert_eq!(settings.target_project, "77789");
+    assert_eq!(settings.screen_id, 2);
+    assert_eq!(settings.ticket_id, 5);
+    assert_eq!(settings.ticket_count, "2");
+    assert_eq!(settings.remind_before_secs, 30);
+}
+
+#[test]
+fn settings_from_newer_version_are_rejected() {
+    let json = serde_json::json!({ "version": SETTINGS_VERSION + 1 });
+    assert!(migrate_settings(json).is_err());
+}
+
+#[test]
+fn profiles_are_created_listed_and_remembered() {
+    let root = std::env::temp_dir().join(format!("bili_profiles_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    assert_eq!(current_profile(&root), DEFAULT_PROFILE);
+
+    create_profile(&root, "工作").unwrap();
+    create_profile(&root, "个人").unwrap();
+    assert!(create_profile(&root, "工作").is_err());
+    assert_eq!(list_profiles(&root), ["个人", "工作"]);
+
+    set_current_profile(&root, "工作").unwrap();
+    assert_eq!(current_profile(&root), "工作");
+    delete_profile(&root, "个人").unwrap();
+    assert_eq!(list_profiles(&root), ["工作"]);
+
+    assert!(validate_profile_name("").is_err());
+    assert!(validate_profile_name("../config").is_err());
+    assert!(validate_profile_name(".hidden").is_err());
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn legacy_files_move_into_default_profile() {
+    let dir = std::env::temp_dir().join(format!("bili_legacy_{}", std::process::id()));
+    let root = dir.join("profiles");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("config.json"), "{}").unwrap();
+
+    assert!(migrate_legacy_files(&root, &dir).unwrap());
+    assert!(!dir.join("config.json").exists());
+    assert!(config_path(&root, DEFAULT_PROFILE).exists());
+    assert!(!credential_path(&root, DEFAULT_PROFILE).exists());
+    assert!(!migrate_legacy_files(&root, &dir).unwrap());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn order(order_id: &str, create_at: i32, sub_status_name: &str) -> Order {
+    let mut order = order_json(order_id, sub_status_name);
+    order["create_at"] = serde_json::json!(create_at);
+    serde_json::from_value(order).unwrap()
+}
+
+#[test]
+fn merge_orders_updates_existing_and_keeps_older_pages() {
+    let mut orders = vec![order("2", 20, "待支付"), order("1", 10, "已完成")];
+    merge_orders(
+        &mut orders,
+        vec![order("3", 30, "待支付"), order("2", 20, "已取消")],
+    );
+    let ids: Vec<_> = orders.iter().map(|o| o.order_id.as_str()).collect();
+    assert_eq!(ids, ["3", "2", "1"]);
+    assert_eq!(orders[1].sub_status_name, "已取消");
+
+    merge_orders(&mut orders, vec![order("0", 5, "已完成")]);
+    assert_eq!(orders.last().unwrap().order_id, "0");
+}
+
+#[test]
+fn payment_stale_follows_selected_order() {
+    let mut unpaid = order("1", 10, "待支付");
+    unpaid.current_time = 1000;
+    unpaid.pay_remain_time = 900;
+    //其它订单是否待支付不影响当前付款码
+    let orders = vec![unpaid, order("2", 20, "待支付"), order("3", 30, "已完成")];
+    assert!(!payment_stale(&orders, "1", 1_800_000));
+    assert!(payment_stale(&orders, "1", 1_900_000));
+    assert!(payment_stale(&orders, "3", 0));
+    assert!(!payment_stale(&orders, "4", 0));
+
+    let paid = vec![order("1", 10, "已完成"), order("2", 20, "待支付")];
+    assert!(payment_stale(&paid, "1", 0));
+}
+
+fn buyer(id: i64, name: &str) -> Buyer {
+    serde_json::from_value(buyer_json(id, name, 0)).unwrap()
+}
+
+#[test]
+fn pick_buyers_requires_one_buyer_per_ticket() {
+    let buyers = vec![buyer(1, "张三"), buyer(2, "李四"), buyer(3, "王五")];
+    let picked = pick_buyers(&buyers, &[3, 1], 2).unwrap();
+    let names: Vec<_> = picked.iter().map(|b| b.name()).collect();
+    assert_eq!(names, ["张三", "王五"]);
+
+    assert!(pick_buyers(&buyers, &[], 1).is_err());
+    assert!(pick_buyers(&buyers, &[1], 2).is_err());
+    assert!(pick_buyers(&buyers, &[1, 4], 2).is_err());
+}
+
+#[test]
+fn qr_image_draws_finder_pattern_inside_quiet_zone() {
+    let image = qr_image("https://passport.bilibili.com/h5-app/passport/login/scan?qrcode_key=key")
+        .unwrap();
+    let [width, height] = image.size;
+    assert_eq!(width, height);
+    assert_eq!(width % 8, 0);
+    //左上角留白为白色，紧接着是定位图案的黑色外框
+    assert_eq!(image.pixels[0], Color32::WHITE);
+    assert_eq!(image.pixels[32 * width + 32], Color32::BLACK);
+}
+
+#[test]
+fn sale_schedule_reminds_then_opens_once() {
+    let mut schedule = SaleSchedule::new(1, 100_000, Duration::from_secs(60), true);
+    assert_eq!(schedule.poll(0), None);
+    assert_eq!(schedule.poll(40_000), Some(ScheduleEvent::Remind));
+    assert_eq!(schedule.poll(41_000), None);
+    assert_eq!(schedule.poll(100_000), Some(ScheduleEvent::Open));
+    assert_eq!(schedule.poll(101_000), None);
+    assert!(schedule.is_opened());
+
+    //打开时已经过了开售时间，直接触发开售，不再提醒
+    let mut late = SaleSchedule::new(1, 100_000, Duration::from_secs(60), false);
+    assert_eq!(late.poll(200_000), Some(ScheduleEvent::Open));
+    assert_eq!(late.poll(200_001), None);
+}
+
+#[test]
+fn countdown_formats_days_and_tenths() {
+    assert_eq!(format_countdown(3_723_450), "01:02:03.4");
+    assert_eq!(format_countdown(90_061_000), "1天 01:01:01.0");
+    assert_eq!(format_countdown(-5), "00:00:00.0");
+}
+
+fn project(is_sale: i32, tickets: &[(i64, i32, bool)], extra_screen: bool) -> Project {
+    let ticket_list: Vec<_> = tickets
+        .iter()
+        .map(|(id, is_sale, clickable)| {
+            ticket_json(*id, &format!("票档{}", id), *is_sale, *clickable)
+        })
+        .collect();
+    let mut screen_list = vec![screen_json(1, "第一天", ticket_list)];
+    if extra_screen {
+        screen_list.push(screen_json(2, "第二天", vec![]));
+    }
+    let mut project = project_json();
+    project["name"] = serde_json::json!("BW");
+    project["is_sale"] = serde_json::json!(is_sale);
+    project["screen_list"] = serde_json::json!(screen_list);
+    serde_json::from_value(project).unwrap()
+}
+
+#[test]
+fn diff_project_reports_sale_changes_and_new_tiers() {
+    let old = project(0, &[(1, 0, false), (2, 1, false)], false);
+    assert!(diff_project(&old, &old).is_empty());
+
+    let new = project(1, &[(1, 1, true), (2, 1, false), (3, 1, true)], true);
+    let changes = diff_project(&old, &new);
+    assert_eq!(
+        changes[0],
+        WatchChange::ProjectSale {
+            project: "BW".to_string(),
+            is_sale: SaleStatus::OnSale
+        }
+    );
+    let purchasable: Vec<String> = changes
+        .iter()
+        .filter(|change| change.became_purchasable())
+        .map(|change| change.to_string())
+        .collect();
+    //票档1的两个状态同时变化只提醒一次，票档2仍不可点击不提醒
+    assert_eq!(
+        purchasable,
+        [
+            "BW 第一天 票档1的售卖状态变为可售",
+            "BW 第一天新增票档票档3"
+        ]
+    );
+    assert!(changes.contains(&WatchChange::NewScreen {
+        project: "BW".to_string(),
+        screen: "第二天".to_string()
+    }));
+    assert_eq!(changes.len(), 5);
+}
+
+#[test]
+fn payment_reminder_warns_once_per_threshold_and_expires() {
+    let mut reminder = PaymentReminder::default();
+    let thresholds = [300, 60];
+    assert_eq!(reminder.poll("1", 600_000, &thresholds), None);
+    assert_eq!(
+        reminder.poll("1", 299_000, &thresholds),
+        Some(PaymentEvent::Warn(300))
+    );
+    assert_eq!(reminder.poll("1", 200_000, &thresholds), None);
+    //一次越过剩下的阈值只提醒一次
+    assert_eq!(
+        reminder.poll("2", 30_000, &thresholds),
+        Some(PaymentEvent::Warn(60))
+    );
+    assert_eq!(reminder.poll("2", 20_000, &thresholds), None);
+    assert_eq!(
+        reminder.poll("1", 0, &thresholds),
+        Some(PaymentEvent::Expired)
+    );
+    assert_eq!(reminder.poll("1", -1000, &thresholds), None);
+
+    reminder.retain(&["2".to_string()]);
+    assert_eq!(
+        reminder.poll("1", -1000, &thresholds),
+        Some(PaymentEvent::Expired)
+    );
+    assert_eq!(format_pay_remain(899_999), "14:59");
+    assert_eq!(format_pay_remain(-5), "00:00");
+}
+
+#[test]
+fn logger_keeps_recent_events_and_rotates_file() {
+    let dir = std::env::temp_dir().join(format!("bili_logs_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("bili_ticket.log");
+    let logger = Logger::new(3, Some(LogFile::new(&path, 120, 2)));
+    logger.log(LogLevel::Info, LogSource::Login, "登录成功!\n");
+    logger.log(LogLevel::Error, LogSource::Order, "购票失败");
+    logger.log(LogLevel::Warn, LogSource::Payment, "付款码已过期");
+    logger.log(LogLevel::Info, LogSource::Project, "载入商品信息完成");
+
+    let events = logger.filtered(LogLevel::Info, "");
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].message, "购票失败");
+    assert!(events[0].to_string().contains("[错误][订单] 购票失败"));
+    //日志时间按本机时区显示
+    let event = LogEvent {
+        time: 1710763200123,
+        ..events[0].clone()
+    };
+    let expected = chrono::Local
+        .timestamp_millis_opt(1710763200123)
+        .unwrap()
+        .format("%Y-%m-%d %H:%M:%S%.3f ")
+        .to_string();
+    assert!(event.to_string().starts_with(&expected));
+    assert_eq!(logger.filtered(LogLevel::Warn, "").len(), 2);
+    assert_eq!(logger.filtered(LogLevel::Info, "支付").len(), 1);
+
+    //每行约60字节，超过120字节就换一个文件，只保留两个旧文件
+    assert!(path.exists());
+    assert!(dir.join("bili_ticket.log.1").exists());
+    assert!(!dir.join("bili_ticket.log.3").exists());
+    let all: String = ["bili_ticket.log.2", "bili_ticket.log.1", "bili_ticket.log"]
+        .iter()
+        .filter_map(|name| std::fs::read_to_string(dir.join(name)).ok())
+        .collect();
+    assert!(all.contains("载入商品信息完成"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,159 @@
+use bili_lib::{Project, SaleStatus};
+use std::fmt;
+use std::time::Duration;
+
+//关注列表刷新间隔不能太短，避免请求过于频繁
+pub const WATCH_MIN_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(PartialEq, Debug)]
+pub enum WatchChange {
+    ProjectSale {
+        project: String,
+        is_sale: SaleStatus,
+    },
+    NewScreen {
+        project: String,
+        screen: String,
+    },
+    NewTicket {
+        project: String,
+        screen: String,
+        ticket: String,
+        purchasable: bool,
+    },
+    TicketClickable {
+        project: String,
+        screen: String,
+        ticket: String,
+        clickable: bool,
+        purchasable: bool,
+    },
+    TicketSale {
+        project: String,
+        screen: String,
+        ticket: String,
+        is_sale: SaleStatus,
+        purchasable: bool,
+    },
+}
+
+impl WatchChange {
+    //票档从不可购买变为可购买，或新增的票档可以直接购买
+    pub fn became_purchasable(&self) -> bool {
+        match self {
+            WatchChange::NewTicket { purchasable, .. }
+            | WatchChange::TicketClickable { purchasable, .. }
+            | WatchChange::TicketSale { purchasable, .. } => *purchasable,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for WatchChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchChange::ProjectSale { project, is_sale } => {
+                write!(f, "{}的售卖状态变为{}", project, is_sale)
+            }
+            WatchChange::NewScreen { project, screen } => {
+                write!(f, "{}新增场次{}", project, screen)
+            }
+            WatchChange::NewTicket {
+                project,
+                screen,
+                ticket,
+                ..
+            } => write!(f, "{} {}新增票档{}", project, screen, ticket),
+            WatchChange::TicketClickable {
+                project,
+                screen,
+                ticket,
+                clickable,
+                ..
+            } => write!(
+                f,
+                "{} {} {}{}",
+                project,
+                screen,
+                ticket,
+                if *clickable {
+                    "可以点击了"
+                } else {
+                    "不可点击"
+                }
+            ),
+            WatchChange::TicketSale {
+                project,
+                screen,
+                ticket,
+                is_sale,
+                ..
+            } => write!(
+                f,
+                "{} {} {}的售卖状态变为{}",
+                project, screen, ticket, is_sale
+            ),
+        }
+    }
+}
+
+//对比同一票品的两次查询结果，列出售卖状态的变化和新增的场次、票档
+pub fn diff_project(old: &Project, new: &Project) -> Vec<WatchChange> {
+    let mut changes = vec![];
+    let project = new.name.clone();
+    if old.is_sale != new.is_sale {
+        changes.push(WatchChange::ProjectSale {
+            project: project.clone(),
+            is_sale: new.is_sale,
+        });
+    }
+    for screen in &new.screen_list {
+        let Some(old_screen) = old.screen_list.iter().find(|s| s.id == screen.id) else {
+            changes.push(WatchChange::NewScreen {
+                project: project.clone(),
+                screen: screen.name.clone(),
+            });
+            for ticket in &screen.ticket_list {
+                changes.push(WatchChange::NewTicket {
+                    project: project.clone(),
+                    screen: screen.name.clone(),
+                    ticket: ticket.desc.clone(),
+                    purchasable: ticket.is_purchasable(),
+                });
+            }
+            continue;
+        };
+        for ticket in &screen.ticket_list {
+            let Some(old_ticket) = old_screen.ticket_list.iter().find(|t| t.id == ticket.id) else {
+                changes.push(WatchChange::NewTicket {
+                    project: project.clone(),
+                    screen: screen.name.clone(),
+                    ticket: ticket.desc.clone(),
+                    purchasable: ticket.is_purchasable(),
+                });
+                continue;
+            };
+            //同一票档的两个状态同时变化时只算一次变为可购买
+            let purchasable = ticket.is_purchasable() && !old_ticket.is_purchasable();
+            if old_ticket.is_sale != ticket.is_sale {
+                changes.push(WatchChange::TicketSale {
+                    project: project.clone(),
+                    screen: screen.name.clone(),
+                    ticket: ticket.desc.clone(),
+                    is_sale: ticket.is_sale,
+                    purchasable,
+                });
+            }
+            if old_ticket.clickable != ticket.clickable {
+                changes.push(WatchChange::TicketClickable {
+                    project: project.clone(),
+                    screen: screen.name.clone(),
+                    ticket: ticket.desc.clone(),
+                    clickable: ticket.clickable,
+                    purchasable: purchasable && old_ticket.is_sale == ticket.is_sale,
+                });
+            }
+        }
+    }
+    changes
+}